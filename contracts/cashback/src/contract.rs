@@ -7,8 +7,8 @@ use cosmwasm_std::{
 };
 
 use crate::msg::{
-    space_pad, ContractStatusLevel, HandleAnswer, HandleMsg, HookMsg, InitMsg, QueryAnswer,
-    QueryMsg, ResponseStatus::Success,
+    ContractStatusLevel, HandleAnswer, HandleMsg, HookMsg, InitMsg, QueryAnswer, QueryMsg,
+    QueryWithPermit, ResponseStatus::Success,
 };
 use crate::rand::sha_256;
 use crate::receiver::Snip20ReceiveMsg;
@@ -20,9 +20,11 @@ use crate::state::{
 };
 use crate::viewing_key::{ViewingKey, VIEWING_KEY_SIZE};
 use scrt_finance::master_msg::MasterHandleMsg;
+use scrt_finance::padding::{pad_handle_result, pad_query_result};
+use scrt_finance::permit::{revoke_permit, validate, Permission, Permit};
 use secret_toolkit::storage::{TypedStore, TypedStoreMut};
 
-/// We make sure that responses from `handle` are padded to a multiple of this size.
+/// We make sure that responses from `handle` and `query` are padded to a multiple of this size.
 pub const RESPONSE_BLOCK_SIZE: usize = 256;
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
@@ -59,6 +61,7 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         decimals: 6,
         admin: admin.clone(),
         prng_seed: prng_seed_hashed.to_vec(),
+        contract_address: env.contract.address,
         total_supply_is_public: true,
     })?;
     config.set_total_supply(total_supply);
@@ -68,16 +71,6 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
     Ok(InitResponse::default())
 }
 
-fn pad_response(response: StdResult<HandleResponse>) -> StdResult<HandleResponse> {
-    response.map(|mut response| {
-        response.data = response.data.map(|mut data| {
-            space_pad(RESPONSE_BLOCK_SIZE, &mut data.0);
-            data
-        });
-        response
-    })
-}
-
 pub fn handle<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -93,7 +86,7 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
                     "This contract is stopped and this action is not allowed",
                 )),
             };
-            return pad_response(response);
+            return pad_handle_result(response, RESPONSE_BLOCK_SIZE);
         }
         ContractStatusLevel::NormalRun => {} // If it's a normal run just continue
     }
@@ -113,6 +106,7 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
         HandleMsg::RegisterReceive { code_hash, .. } => try_register_receive(deps, env, code_hash),
         HandleMsg::CreateViewingKey { entropy, .. } => try_create_key(deps, env, entropy),
         HandleMsg::SetViewingKey { key, .. } => try_set_key(deps, env, key),
+        HandleMsg::RevokePermit { permit_name, .. } => try_revoke_permit(deps, env, permit_name),
         HandleMsg::IncreaseAllowance {
             spender,
             amount,
@@ -154,16 +148,19 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
         HandleMsg::SetContractStatus { level, .. } => set_contract_status(deps, env, level),
     };
 
-    pad_response(response)
+    pad_handle_result(response, RESPONSE_BLOCK_SIZE)
 }
 
 pub fn query<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, msg: QueryMsg) -> QueryResult {
-    match msg {
+    let response = match msg {
         QueryMsg::TokenInfo {} => query_token_info(&deps.storage),
         QueryMsg::ExchangeRate {} => query_exchange_rate(),
         QueryMsg::Minters { .. } => query_minters(deps),
+        QueryMsg::WithPermit { permit, query } => permit_queries(deps, permit, query),
         _ => authenticated_queries(deps, msg),
-    }
+    };
+
+    pad_query_result(response, RESPONSE_BLOCK_SIZE)
 }
 
 pub fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
@@ -204,6 +201,51 @@ pub fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
     })?)
 }
 
+fn permit_queries<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    permit: Permit,
+    query: QueryWithPermit,
+) -> QueryResult {
+    let contract_address = ReadonlyConfig::from_storage(&deps.storage)
+        .constants()?
+        .contract_address;
+    let account = validate(&deps.storage, &permit, &contract_address)?;
+
+    match query {
+        QueryWithPermit::Balance {} => {
+            if !permit.check_permission(&Permission::Balance) {
+                return Err(permission_err(&permit, "balance"));
+            }
+            query_balance(deps, &account)
+        }
+        QueryWithPermit::TransferHistory { page, page_size } => {
+            if !permit.check_permission(&Permission::History) {
+                return Err(permission_err(&permit, "history"));
+            }
+            query_transactions(deps, &account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::Allowance { owner, spender } => {
+            if !permit.check_permission(&Permission::Allowance) {
+                return Err(permission_err(&permit, "allowance"));
+            }
+            if account != owner && account != spender {
+                return Err(StdError::generic_err(format!(
+                    "Cannot query allowance. Requires permit for either owner {:?} or spender {:?}, got permit for {:?}",
+                    owner.0, spender.0, account.0
+                )));
+            }
+            try_check_allowance(deps, owner, spender)
+        }
+    }
+}
+
+fn permission_err(permit: &Permit, permission: &str) -> StdError {
+    StdError::generic_err(format!(
+        "No permission to query {}, got permissions {:?}",
+        permission, permit.params.permissions
+    ))
+}
+
 /// This function just returns a constant 1:1 rate to uscrt, since that's the purpose of this
 /// contract.
 fn query_exchange_rate() -> QueryResult {
@@ -355,6 +397,20 @@ pub fn try_set_key<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+fn try_revoke_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    permit_name: String,
+) -> StdResult<HandleResponse> {
+    revoke_permit(&mut deps.storage, &env.message.sender, &permit_name);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokePermit { status: Success })?),
+    })
+}
+
 pub fn try_create_key<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -1029,6 +1085,7 @@ mod tests {
     use crate::msg::{InitConfig, InitialBalance};
     use cosmwasm_std::testing::*;
     use cosmwasm_std::{from_binary, BlockInfo, ContractInfo, MessageInfo, QueryResponse, WasmMsg};
+    use scrt_finance::permit::{PermitParams, PermitSignature, PubKey};
     use std::any::Any;
 
     // Helper functions
@@ -1109,6 +1166,7 @@ mod tests {
             | HandleAnswer::Burn { status }
             | HandleAnswer::RegisterReceive { status }
             | HandleAnswer::SetViewingKey { status }
+            | HandleAnswer::RevokePermit { status }
             | HandleAnswer::TransferFrom { status }
             | HandleAnswer::SendFrom { status }
             | HandleAnswer::BurnFrom { status }
@@ -2605,6 +2663,48 @@ mod tests {
         assert_eq!(balance, Uint128(5000));
     }
 
+    #[test]
+    fn test_query_responses_are_padded() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let queries = vec![
+            QueryMsg::TokenInfo {},
+            QueryMsg::Balance {
+                address: HumanAddr("bob".to_string()),
+                key: "key".to_string(),
+            },
+            QueryMsg::Balance {
+                address: HumanAddr("bob".to_string()),
+                key: "wrong_key".to_string(),
+            },
+            QueryMsg::TransferHistory {
+                address: HumanAddr("bob".to_string()),
+                key: "key".to_string(),
+                page: None,
+                page_size: 10,
+            },
+        ];
+        for query_msg in queries {
+            let response = query(&deps, query_msg).unwrap();
+            assert_eq!(response.len() % RESPONSE_BLOCK_SIZE, 0);
+        }
+    }
+
     #[test]
     fn test_query_transfer_history() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
@@ -2690,4 +2790,79 @@ mod tests {
         };
         assert_eq!(transfers.len(), 2);
     }
+
+    #[test]
+    fn test_query_with_permit() {
+        let signer = HumanAddr("secret1wv66lgmt6azsngtdcqrkgyt7hjsk3r9lkvzqcx".to_string());
+        // Signer addresses are longer than the usual mock ones
+        let mut deps = mock_dependencies(45, &[]);
+        let init_msg = InitMsg {
+            name: "sec-sec".to_string(),
+            admin: Some(HumanAddr("admin".to_string())),
+            symbol: "SECSEC".to_string(),
+            decimals: 8,
+            initial_balances: Some(vec![InitialBalance {
+                address: signer.clone(),
+                amount: Uint128(5000),
+            }]),
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            config: None,
+        };
+        init(&mut deps, mock_env("instantiator", &[]), init_msg).unwrap();
+
+        // Signed for this contract, with the `balance` permission only
+        let permit = Permit {
+            params: PermitParams {
+                allowed_tokens: vec![HumanAddr(MOCK_CONTRACT_ADDR.to_string())],
+                permit_name: "test".to_string(),
+                chain_id: "secret-4".to_string(),
+                permissions: vec![Permission::Balance],
+            },
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary::from_base64("Aq5U7xmLb4QZjttvvflKkSN8wD9ywJ8brS/IhFKlww5F")
+                        .unwrap(),
+                },
+                signature: Binary::from_base64(
+                    "q+oy5eoKpsf+shFw54rqYrc5iCnWNV1AQzQq3D1z7wshXl7gr5Qrar9l+pOdGBHk+a66+5bj64WhB9bthpH1ug==",
+                )
+                .unwrap(),
+            },
+        };
+
+        let query_msg = QueryMsg::WithPermit {
+            permit: permit.clone(),
+            query: QueryWithPermit::Balance {},
+        };
+        let balance = match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::Balance { amount } => amount,
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(balance, Uint128(5000));
+
+        let query_msg = QueryMsg::WithPermit {
+            permit: permit.clone(),
+            query: QueryWithPermit::TransferHistory {
+                page: None,
+                page_size: 10,
+            },
+        };
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("No permission to query history"));
+
+        let handle_msg = HandleMsg::RevokePermit {
+            permit_name: "test".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env(signer.0.as_str(), &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_msg = QueryMsg::WithPermit {
+            permit,
+            query: QueryWithPermit::Balance {},
+        };
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("was revoked"));
+    }
 }
@@ -5,6 +5,7 @@ use cosmwasm_std::{Binary, HumanAddr, StdError, StdResult, Uint128};
 
 use crate::state::Tx;
 use crate::viewing_key::ViewingKey;
+use scrt_finance::permit::Permit;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct InitialBalance {
@@ -66,6 +67,10 @@ pub enum HandleMsg {
         key: String,
         padding: Option<String>,
     },
+    RevokePermit {
+        permit_name: String,
+        padding: Option<String>,
+    },
 
     // Allowance
     IncreaseAllowance {
@@ -179,6 +184,9 @@ pub enum HandleAnswer {
     SetViewingKey {
         status: ResponseStatus,
     },
+    RevokePermit {
+        status: ResponseStatus,
+    },
 
     // Allowance
     IncreaseAllowance {
@@ -245,6 +253,10 @@ pub enum QueryMsg {
         page_size: u32,
     },
     Minters {},
+    WithPermit {
+        permit: Permit,
+        query: QueryWithPermit,
+    },
 }
 
 impl QueryMsg {
@@ -263,6 +275,22 @@ impl QueryMsg {
     }
 }
 
+/// Authenticated queries that can be made with a SNIP-24 permit instead of a viewing key. The
+/// address is the one that signed the permit
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryWithPermit {
+    Allowance {
+        owner: HumanAddr,
+        spender: HumanAddr,
+    },
+    Balance {},
+    TransferHistory {
+        page: Option<u32>,
+        page_size: u32,
+    },
+}
+
 #[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryAnswer {
@@ -334,20 +362,6 @@ pub fn u8_to_status_level(status_level: u8) -> StdResult<ContractStatusLevel> {
     }
 }
 
-// Take a Vec<u8> and pad it up to a multiple of `block_size`, using spaces at the end.
-pub fn space_pad(block_size: usize, message: &mut Vec<u8>) -> &mut Vec<u8> {
-    let len = message.len();
-    let surplus = len % block_size;
-    if surplus == 0 {
-        return message;
-    }
-
-    let missing = block_size - surplus;
-    message.reserve(missing);
-    message.extend(std::iter::repeat(b' ').take(missing));
-    message
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
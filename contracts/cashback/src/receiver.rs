@@ -3,7 +3,8 @@ use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{to_binary, Binary, CosmosMsg, HumanAddr, StdResult, Uint128, WasmMsg};
 
-use crate::{contract::RESPONSE_BLOCK_SIZE, msg::space_pad};
+use crate::contract::RESPONSE_BLOCK_SIZE;
+use scrt_finance::padding::space_pad;
 
 /// Snip20ReceiveMsg should be de/serialized under `Receive()` variant in a HandleMsg
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -165,6 +165,8 @@ pub struct Constants {
     pub symbol: String,
     pub decimals: u8,
     pub prng_seed: Vec<u8>,
+    // Needed to validate query permits, since queries don't get an env
+    pub contract_address: HumanAddr,
     // privacy configuration
     pub total_supply_is_public: bool,
 }
@@ -1,25 +1,31 @@
 /// This contract implements SNIP-20 standard:
 /// https://github.com/SecretFoundation/SNIPs/blob/master/SNIP-20.md
 use cosmwasm_std::{
-    log, to_binary, Api,  Binary, CanonicalAddr,  CosmosMsg, Env, Extern,
+    log, to_binary, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg, Env, Extern,
     HandleResponse, HumanAddr, InitResponse, Querier, QueryResult, ReadonlyStorage, StdError,
     StdResult, Storage, Uint128,
 };
 
+use crate::batch;
 use crate::msg::{
-    space_pad, ContractStatusLevel, HandleAnswer, HandleMsg, InitMsg, QueryAnswer, QueryMsg,
-    ResponseStatus::Success,
+    AllowanceGiven, AllowanceReceived, ContractStatusLevel, HandleAnswer, HandleMsg, InitMsg,
+    MinterQuota, QueryAnswer, QueryMsg, QueryWithPermit, ResponseStatus::Success,
 };
 use crate::rand::sha_256;
 use crate::receiver::Snip20ReceiveMsg;
 use crate::state::{
-    get_receiver_hash, get_transfers, read_allowance, read_viewing_key, set_receiver_hash,
-    store_transfer, write_allowance, write_viewing_key, Balances, Config, Constants,
-    ReadonlyBalances, ReadonlyConfig,
+    get_allowances_given, get_allowances_received, get_receiver_hash, get_total_voting_power,
+    get_transactions, get_transfers, get_voting_power, move_voting_power, read_allowance,
+    read_delegate, read_minter_quota, read_viewing_key, set_receiver_hash, store_burn,
+    store_deposit, store_mint, store_redeem, store_transfer, use_minter_quota, write_allowance,
+    write_delegate, write_minter_quota, write_total_supply_checkpoint, write_viewing_key, Balances,
+    Config, Constants, MinterQuotaState, ReadonlyBalances, ReadonlyConfig,
 };
 use crate::viewing_key::{ViewingKey, VIEWING_KEY_SIZE};
+use scrt_finance::padding::{pad_handle_result, pad_query_result};
+use scrt_finance::permit::{revoke_permit, validate, Permission, Permit};
 
-/// We make sure that responses from `handle` are padded to a multiple of this size.
+/// We make sure that responses from `handle` and `query` are padded to a multiple of this size.
 pub const RESPONSE_BLOCK_SIZE: usize = 256;
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
@@ -61,6 +67,13 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         return Err(StdError::generic_err("Decimals must not exceed 18"));
     }
 
+    let max_supply = init_config.max_supply();
+    if max_supply.map_or(false, |max_supply| total_supply > max_supply) {
+        return Err(StdError::generic_err(
+            "The sum of all initial balances exceeds the max supply",
+        ));
+    }
+
     let admin = msg.admin.unwrap_or_else(|| env.message.sender);
 
     let prng_seed_hashed = sha_256(&msg.prng_seed.0);
@@ -72,25 +85,19 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         decimals: msg.decimals,
         admin: admin.clone(),
         prng_seed: prng_seed_hashed.to_vec(),
+        contract_address: env.contract.address,
         total_supply_is_public: init_config.public_total_supply(),
+        max_supply,
+        native_denom: init_config.native_denom(),
     })?;
     config.set_total_supply(total_supply);
     config.set_contract_status(ContractStatusLevel::NormalRun);
     config.set_minters(Vec::from([admin]))?;
+    write_total_supply_checkpoint(&mut deps.storage, total_supply, env.block.height)?;
 
     Ok(InitResponse::default())
 }
 
-fn pad_response(response: StdResult<HandleResponse>) -> StdResult<HandleResponse> {
-    response.map(|mut response| {
-        response.data = response.data.map(|mut data| {
-            space_pad(RESPONSE_BLOCK_SIZE, &mut data.0);
-            data
-        });
-        response
-    })
-}
-
 pub fn handle<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -102,30 +109,44 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
         ContractStatusLevel::StopAll | ContractStatusLevel::StopAllButRedeems => {
             let response = match msg {
                 HandleMsg::SetContractStatus { level, .. } => set_contract_status(deps, env, level),
+                HandleMsg::Redeem { amount, denom, .. }
+                    if contract_status == ContractStatusLevel::StopAllButRedeems =>
+                {
+                    try_redeem(deps, env, amount, denom)
+                }
                 _ => Err(StdError::generic_err(
                     "This contract is stopped and this action is not allowed",
                 )),
             };
-            return pad_response(response);
+            return pad_handle_result(response, RESPONSE_BLOCK_SIZE);
         }
         ContractStatusLevel::NormalRun => {} // If it's a normal run just continue
     }
 
     let response = match msg {
+        // Native
+        HandleMsg::Deposit { .. } => try_deposit(deps, env),
+        HandleMsg::Redeem { amount, denom, .. } => try_redeem(deps, env, amount, denom),
+
         // Base
         HandleMsg::Transfer {
-            recipient, amount, ..
-        } => try_transfer(deps, env, &recipient, amount),
+            recipient,
+            amount,
+            memo,
+            ..
+        } => try_transfer(deps, env, &recipient, amount, memo),
         HandleMsg::Send {
             recipient,
             amount,
             msg,
+            memo,
             ..
-        } => try_send(deps, env, &recipient, amount, msg),
+        } => try_send(deps, env, &recipient, amount, msg, memo),
         HandleMsg::Burn { amount, .. } => try_burn(deps, env, amount),
         HandleMsg::RegisterReceive { code_hash, .. } => try_register_receive(deps, env, code_hash),
         HandleMsg::CreateViewingKey { entropy, .. } => try_create_key(deps, env, entropy),
         HandleMsg::SetViewingKey { key, .. } => try_set_key(deps, env, key),
+        HandleMsg::RevokePermit { permit_name, .. } => try_revoke_permit(deps, env, permit_name),
 
         // Allowance
         HandleMsg::IncreaseAllowance {
@@ -144,21 +165,34 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
             owner,
             recipient,
             amount,
+            memo,
             ..
-        } => try_transfer_from(deps, env, &owner, &recipient, amount),
+        } => try_transfer_from(deps, env, &owner, &recipient, amount, memo),
         HandleMsg::SendFrom {
             owner,
             recipient,
             amount,
             msg,
+            memo,
             ..
-        } => try_send_from(deps, env, &owner, &recipient, amount, msg),
+        } => try_send_from(deps, env, &owner, &recipient, amount, msg, memo),
         HandleMsg::BurnFrom { owner, amount, .. } => try_burn_from(deps, env, &owner, amount),
 
+        // Batch
+        HandleMsg::BatchTransfer { actions, .. } => try_batch_transfer(deps, env, actions),
+        HandleMsg::BatchSend { actions, .. } => try_batch_send(deps, env, actions),
+        HandleMsg::BatchTransferFrom { actions, .. } => try_batch_transfer_from(deps, env, actions),
+        HandleMsg::BatchSendFrom { actions, .. } => try_batch_send_from(deps, env, actions),
+        HandleMsg::BatchBurnFrom { actions, .. } => try_batch_burn_from(deps, env, actions),
+        HandleMsg::BatchMint { actions, .. } => try_batch_mint(deps, env, actions),
+
         // Mint
         HandleMsg::Mint {
-            recipient, amount, ..
-        } => try_mint(deps, env, recipient, amount),
+            recipient,
+            amount,
+            memo,
+            ..
+        } => try_mint(deps, env, recipient, amount, memo),
 
         // Other
         HandleMsg::ChangeAdmin { address, .. } => change_admin(deps, env, address),
@@ -166,18 +200,30 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
         HandleMsg::AddMinters { minters, .. } => add_minters(deps, env, minters),
         HandleMsg::RemoveMinters { minters, .. } => remove_minters(deps, env, minters),
         HandleMsg::SetMinters { minters, .. } => set_minters(deps, env, minters),
+        HandleMsg::SetMinterQuota { minter, quota, .. } => {
+            set_minter_quota(deps, env, minter, quota)
+        }
+
+        // Governance
+        HandleMsg::Delegate { delegatee, .. } => try_delegate(deps, env, delegatee),
     };
 
-    pad_response(response)
+    pad_handle_result(response, RESPONSE_BLOCK_SIZE)
 }
 
 pub fn query<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, msg: QueryMsg) -> QueryResult {
-    match msg {
+    let response = match msg {
         QueryMsg::TokenInfo {} => query_token_info(&deps.storage),
-        QueryMsg::ExchangeRate {} => query_exchange_rate(),
+        QueryMsg::ExchangeRate {} => query_exchange_rate(&deps.storage),
         QueryMsg::Minters { .. } => query_minters(deps),
+        QueryMsg::MaxSupply {} => query_max_supply(&deps.storage),
+        QueryMsg::MinterQuota { minter } => query_minter_quota(deps, &minter),
+        QueryMsg::TotalVotingPowerAt { block } => query_total_voting_power(&deps.storage, block),
+        QueryMsg::WithPermit { permit, query } => permit_queries(deps, permit, query),
         _ => authenticated_queries(deps, msg),
-    }
+    };
+
+    pad_query_result(response, RESPONSE_BLOCK_SIZE)
 }
 
 pub fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
@@ -204,10 +250,31 @@ pub fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
                     page,
                     page_size,
                     ..
+                } => query_transfers(&deps, &address, page.unwrap_or(0), page_size),
+                QueryMsg::TransactionHistory {
+                    address,
+                    page,
+                    page_size,
+                    ..
                 } => query_transactions(&deps, &address, page.unwrap_or(0), page_size),
                 QueryMsg::Allowance { owner, spender, .. } => {
                     try_check_allowance(deps, owner, spender)
                 }
+                QueryMsg::AllowancesGiven {
+                    owner,
+                    page,
+                    page_size,
+                    ..
+                } => query_allowances_given(deps, owner, page.unwrap_or(0), page_size),
+                QueryMsg::AllowancesReceived {
+                    spender,
+                    page,
+                    page_size,
+                    ..
+                } => query_allowances_received(deps, spender, page.unwrap_or(0), page_size),
+                QueryMsg::VotingPower { address, block, .. } => {
+                    query_voting_power(deps, &address, block)
+                }
                 _ => panic!("This query type does not require authentication"),
             };
         }
@@ -218,13 +285,91 @@ pub fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
     })?)
 }
 
-/// This function just returns a constant 1:1 rate to uscrt, since that's the purpose of this
-/// contract.
-fn query_exchange_rate() -> QueryResult {
-    to_binary(&QueryAnswer::ExchangeRate {
-        rate: Uint128(1),
-        denom: "uscrt".to_string(),
-    })
+fn permit_queries<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    permit: Permit,
+    query: QueryWithPermit,
+) -> QueryResult {
+    let contract_address = ReadonlyConfig::from_storage(&deps.storage)
+        .constants()?
+        .contract_address;
+    let account = validate(&deps.storage, &permit, &contract_address)?;
+
+    match query {
+        QueryWithPermit::Balance {} => {
+            if !permit.check_permission(&Permission::Balance) {
+                return Err(permission_err(&permit, "balance"));
+            }
+            query_balance(deps, &account)
+        }
+        QueryWithPermit::TransferHistory { page, page_size } => {
+            if !permit.check_permission(&Permission::History) {
+                return Err(permission_err(&permit, "history"));
+            }
+            query_transfers(deps, &account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::TransactionHistory { page, page_size } => {
+            if !permit.check_permission(&Permission::History) {
+                return Err(permission_err(&permit, "history"));
+            }
+            query_transactions(deps, &account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::Allowance { owner, spender } => {
+            if !permit.check_permission(&Permission::Allowance) {
+                return Err(permission_err(&permit, "allowance"));
+            }
+            if account != owner && account != spender {
+                return Err(StdError::generic_err(format!(
+                    "Cannot query allowance. Requires permit for either owner {:?} or spender {:?}, got permit for {:?}",
+                    owner.0, spender.0, account.0
+                )));
+            }
+            try_check_allowance(deps, owner, spender)
+        }
+        QueryWithPermit::AllowancesGiven { page, page_size } => {
+            if !permit.check_permission(&Permission::Allowance) {
+                return Err(permission_err(&permit, "allowance"));
+            }
+            query_allowances_given(deps, account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::AllowancesReceived { page, page_size } => {
+            if !permit.check_permission(&Permission::Allowance) {
+                return Err(permission_err(&permit, "allowance"));
+            }
+            query_allowances_received(deps, account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::VotingPower { block } => {
+            if !permit.check_permission(&Permission::Balance) {
+                return Err(permission_err(&permit, "voting power"));
+            }
+            query_voting_power(deps, &account, block)
+        }
+    }
+}
+
+fn permission_err(permit: &Permit, permission: &str) -> StdError {
+    StdError::generic_err(format!(
+        "No permission to query {}, got permissions {:?}",
+        permission, permit.params.permissions
+    ))
+}
+
+/// Deposited tokens are redeemed 1:1 for the native denom, up to the native reserve held by the
+/// contract. Tokens that don't wrap a native coin report a rate of 0, as SNIP-20 requires.
+fn query_exchange_rate<S: ReadonlyStorage>(storage: &S) -> QueryResult {
+    let constants = ReadonlyConfig::from_storage(storage).constants()?;
+
+    let response = match constants.native_denom {
+        Some(denom) => QueryAnswer::ExchangeRate {
+            rate: Uint128(1),
+            denom,
+        },
+        None => QueryAnswer::ExchangeRate {
+            rate: Uint128(0),
+            denom: String::new(),
+        },
+    };
+    to_binary(&response)
 }
 
 fn query_token_info<S: ReadonlyStorage>(storage: &S) -> QueryResult {
@@ -245,7 +390,7 @@ fn query_token_info<S: ReadonlyStorage>(storage: &S) -> QueryResult {
     })
 }
 
-pub fn query_transactions<S: Storage, A: Api, Q: Querier>(
+pub fn query_transfers<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     account: &HumanAddr,
     page: u32,
@@ -258,6 +403,22 @@ pub fn query_transactions<S: Storage, A: Api, Q: Querier>(
     to_binary(&result)
 }
 
+pub fn query_transactions<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let address = deps.api.canonical_address(account)?;
+    let (txs, total) = get_transactions(&deps.api, &deps.storage, &address, page, page_size)?;
+
+    let result = QueryAnswer::TransactionHistory {
+        txs,
+        total: Some(total),
+    };
+    to_binary(&result)
+}
+
 pub fn query_balance<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     account: &HumanAddr,
@@ -269,6 +430,32 @@ pub fn query_balance<S: Storage, A: Api, Q: Querier>(
     to_binary(&response)
 }
 
+pub fn query_voting_power<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+    block: Option<u64>,
+) -> StdResult<Binary> {
+    let address = deps.api.canonical_address(account)?;
+
+    let voting_power = Uint128(get_voting_power(&deps.storage, &address, block)?);
+    to_binary(&QueryAnswer::VotingPower { voting_power })
+}
+
+fn query_total_voting_power<S: ReadonlyStorage>(storage: &S, block: u64) -> QueryResult {
+    // The total voting power is the total supply, so it's only as public as the supply is
+    if !ReadonlyConfig::from_storage(storage)
+        .constants()?
+        .total_supply_is_public
+    {
+        return Err(StdError::generic_err(
+            "The total supply of this token is private",
+        ));
+    }
+
+    let total_voting_power = Uint128(get_total_voting_power(storage, Some(block))?);
+    to_binary(&QueryAnswer::TotalVotingPowerAt { total_voting_power })
+}
+
 fn query_minters<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
     let minters = ReadonlyConfig::from_storage(&deps.storage).minters();
 
@@ -276,6 +463,40 @@ fn query_minters<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdR
     to_binary(&response)
 }
 
+fn query_max_supply<S: ReadonlyStorage>(storage: &S) -> QueryResult {
+    let max_supply = ReadonlyConfig::from_storage(storage)
+        .constants()?
+        .max_supply
+        .map(Uint128);
+
+    to_binary(&QueryAnswer::MaxSupply { max_supply })
+}
+
+fn query_minter_quota<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    minter: &HumanAddr,
+) -> StdResult<Binary> {
+    let minter = deps.api.canonical_address(minter)?;
+
+    // Queries don't know the current block, so the window might have already ended
+    let response = match read_minter_quota(&deps.storage, &minter)? {
+        Some(quota) => QueryAnswer::MinterQuota {
+            quota: Some(MinterQuota {
+                amount: Uint128(quota.amount),
+                window: quota.window,
+            }),
+            window_start: Some(quota.window_start),
+            minted_in_window: Uint128(quota.minted),
+        },
+        None => QueryAnswer::MinterQuota {
+            quota: None,
+            window_start: None,
+            minted_in_window: Uint128(0),
+        },
+    };
+    to_binary(&response)
+}
+
 fn change_admin<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -296,40 +517,46 @@ fn change_admin<S: Storage, A: Api, Q: Querier>(
     })
 }
 
-fn try_mint<S: Storage, A: Api, Q: Querier>(
+fn try_mint_impl<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    env: Env,
-    address: HumanAddr,
+    env: &Env,
+    recipient: &HumanAddr,
     amount: Uint128,
-) -> StdResult<HandleResponse> {
-    let mut config = Config::from_storage(&mut deps.storage);
+    memo: Option<String>,
+) -> StdResult<()> {
+    let amount_raw = amount.u128();
 
-    let minters = config.minters();
-    if !minters.contains(&env.message.sender) {
-        return Err(StdError::generic_err(
-            "Minting is allowed to minter accounts only",
-        ));
-    }
+    let minter = deps.api.canonical_address(&env.message.sender)?;
+    use_minter_quota(&mut deps.storage, &minter, amount_raw, env.block.height)?;
 
-    let amount = amount.u128();
+    let mut config = Config::from_storage(&mut deps.storage);
+    let constants = config.constants()?;
 
     let mut total_supply = config.total_supply();
-    if let Some(new_total_supply) = total_supply.checked_add(amount) {
+    if let Some(new_total_supply) = total_supply.checked_add(amount_raw) {
         total_supply = new_total_supply;
     } else {
         return Err(StdError::generic_err(
             "This mint attempt would increase the total supply above the supported maximum",
         ));
     }
+    if let Some(max_supply) = constants.max_supply {
+        if total_supply > max_supply {
+            return Err(StdError::generic_err(format!(
+                "This mint attempt would increase the total supply above the max supply of {}",
+                max_supply
+            )));
+        }
+    }
     config.set_total_supply(total_supply);
 
-    let receipient_account = &deps.api.canonical_address(&address)?;
+    let receipient_account = &deps.api.canonical_address(recipient)?;
 
     let mut balances = Balances::from_storage(&mut deps.storage);
 
     let mut account_balance = balances.balance(receipient_account);
 
-    if let Some(new_balance) = account_balance.checked_add(amount) {
+    if let Some(new_balance) = account_balance.checked_add(amount_raw) {
         account_balance = new_balance;
     } else {
         // This error literally can not happen, since the account's funds are a subset
@@ -343,6 +570,37 @@ fn try_mint<S: Storage, A: Api, Q: Querier>(
 
     balances.set_account_balance(receipient_account, account_balance);
 
+    let delegatee = read_delegate(&deps.storage, receipient_account)?;
+    move_voting_power(
+        &mut deps.storage,
+        None,
+        delegatee.as_ref(),
+        amount_raw,
+        env.block.height,
+    )?;
+    write_total_supply_checkpoint(&mut deps.storage, total_supply, env.block.height)?;
+
+    store_mint(
+        &mut deps.storage,
+        &minter,
+        receipient_account,
+        amount,
+        constants.symbol,
+        memo,
+        &env.block,
+    )
+}
+
+fn try_mint<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    amount: Uint128,
+    memo: Option<String>,
+) -> StdResult<HandleResponse> {
+    check_if_minter(&deps.storage, &env.message.sender)?;
+    try_mint_impl(deps, &env, &address, amount, memo)?;
+
     let res = HandleResponse {
         messages: vec![],
         log: vec![],
@@ -352,6 +610,25 @@ fn try_mint<S: Storage, A: Api, Q: Querier>(
     Ok(res)
 }
 
+fn try_batch_mint<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    actions: Vec<batch::MintAction>,
+) -> StdResult<HandleResponse> {
+    check_if_minter(&deps.storage, &env.message.sender)?;
+    for action in actions {
+        try_mint_impl(deps, &env, &action.recipient, action.amount, action.memo)?;
+    }
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::BatchMint { status: Success })?),
+    };
+
+    Ok(res)
+}
+
 pub fn try_set_key<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -369,6 +646,20 @@ pub fn try_set_key<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+fn try_revoke_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    permit_name: String,
+) -> StdResult<HandleResponse> {
+    revoke_permit(&mut deps.storage, &env.message.sender, &permit_name);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokePermit { status: Success })?),
+    })
+}
+
 pub fn try_create_key<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -428,11 +719,241 @@ pub fn try_check_allowance<S: Storage, A: Api, Q: Querier>(
     to_binary(&response)
 }
 
-fn try_transfer_impl<S: Storage, A: Api, Q: Querier>(
+fn query_allowances_given<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let owner_address = deps.api.canonical_address(&owner)?;
+    let (allowances, count) =
+        get_allowances_given(&deps.api, &deps.storage, &owner_address, page, page_size)?;
+
+    let response = QueryAnswer::AllowancesGiven {
+        owner,
+        allowances: allowances
+            .into_iter()
+            .map(|(spender, allowance)| AllowanceGiven {
+                spender,
+                allowance: Uint128(allowance.amount),
+                expiration: allowance.expiration,
+            })
+            .collect(),
+        count,
+    };
+    to_binary(&response)
+}
+
+fn query_allowances_received<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    spender: HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let spender_address = deps.api.canonical_address(&spender)?;
+    let (allowances, count) =
+        get_allowances_received(&deps.api, &deps.storage, &spender_address, page, page_size)?;
+
+    let response = QueryAnswer::AllowancesReceived {
+        spender,
+        allowances: allowances
+            .into_iter()
+            .map(|(owner, allowance)| AllowanceReceived {
+                owner,
+                allowance: Uint128(allowance.amount),
+                expiration: allowance.expiration,
+            })
+            .collect(),
+        count,
+    };
+    to_binary(&response)
+}
+
+fn try_deposit<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+    let constants = config.constants()?;
+    let denom = native_denom(&constants)?;
+
+    let mut amount = Uint128(0);
+    for coin in &env.message.sent_funds {
+        if coin.denom == denom {
+            amount = coin.amount
+        } else {
+            return Err(StdError::generic_err(format!(
+                "Tried to deposit an unsupported token, only {} is accepted",
+                denom
+            )));
+        }
+    }
+
+    if amount.u128() == 0 {
+        return Err(StdError::generic_err("No funds were sent to be deposited"));
+    }
+
+    let raw_amount = amount.u128();
+
+    let mut total_supply = config.total_supply();
+    if let Some(new_total_supply) = total_supply.checked_add(raw_amount) {
+        total_supply = new_total_supply;
+    } else {
+        return Err(StdError::generic_err(
+            "This deposit would overflow the currency's total supply",
+        ));
+    }
+    if let Some(max_supply) = constants.max_supply {
+        if total_supply > max_supply {
+            return Err(StdError::generic_err(format!(
+                "This deposit would increase the total supply above the max supply of {}",
+                max_supply
+            )));
+        }
+    }
+    config.set_total_supply(total_supply);
+    // The supply check above bounds the reserve too, so this can't overflow
+    let native_reserve = config.native_reserve() + raw_amount;
+    config.set_native_reserve(native_reserve)?;
+
+    let sender_address = deps.api.canonical_address(&env.message.sender)?;
+
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let account_balance = balances.balance(&sender_address);
+    if let Some(account_balance) = account_balance.checked_add(raw_amount) {
+        balances.set_account_balance(&sender_address, account_balance);
+    } else {
+        // This error literally can not happen, since the account's funds are a subset
+        // of the total supply, both are stored as u128, and we check for overflow of
+        // the total supply just a couple lines before.
+        // Still, writing this to cover all overflows.
+        return Err(StdError::generic_err(
+            "This deposit would overflow your balance",
+        ));
+    }
+
+    let delegatee = read_delegate(&deps.storage, &sender_address)?;
+    move_voting_power(
+        &mut deps.storage,
+        None,
+        delegatee.as_ref(),
+        raw_amount,
+        env.block.height,
+    )?;
+    write_total_supply_checkpoint(&mut deps.storage, total_supply, env.block.height)?;
+
+    store_deposit(
+        &mut deps.storage,
+        &sender_address,
+        amount,
+        denom,
+        &env.block,
+    )?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Deposit { status: Success })?),
+    };
+
+    Ok(res)
+}
+
+fn try_redeem<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    amount: Uint128,
+    denom: Option<String>,
+) -> StdResult<HandleResponse> {
+    let constants = ReadonlyConfig::from_storage(&deps.storage).constants()?;
+    let native_denom = native_denom(&constants)?;
+    if denom.map_or(false, |denom| denom != native_denom) {
+        return Err(StdError::generic_err(format!(
+            "Tokens can only be redeemed for {}",
+            native_denom
+        )));
+    }
+
+    let sender_address = deps.api.canonical_address(&env.message.sender)?;
+    let amount_raw = amount.u128();
+
+    // Only deposited tokens are backed by native coin. Minted tokens must not be able to drain it.
+    let mut config = Config::from_storage(&mut deps.storage);
+    let native_reserve = config.native_reserve();
+    if let Some(native_reserve) = native_reserve.checked_sub(amount_raw) {
+        config.set_native_reserve(native_reserve)?;
+    } else {
+        return Err(StdError::generic_err(format!(
+            "insufficient native reserve to redeem: reserve={}, required={}",
+            native_reserve, amount_raw
+        )));
+    }
+
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let account_balance = balances.balance(&sender_address);
+
+    if let Some(account_balance) = account_balance.checked_sub(amount_raw) {
+        balances.set_account_balance(&sender_address, account_balance);
+    } else {
+        return Err(StdError::generic_err(format!(
+            "insufficient funds to redeem: balance={}, required={}",
+            account_balance, amount_raw
+        )));
+    }
+
+    let mut config = Config::from_storage(&mut deps.storage);
+    let mut total_supply = config.total_supply();
+    if let Some(new_total_supply) = total_supply.checked_sub(amount_raw) {
+        total_supply = new_total_supply;
+    } else {
+        return Err(StdError::generic_err(
+            "You are trying to redeem more tokens than what is available in the total supply",
+        ));
+    }
+    config.set_total_supply(total_supply);
+
+    let delegatee = read_delegate(&deps.storage, &sender_address)?;
+    move_voting_power(
+        &mut deps.storage,
+        delegatee.as_ref(),
+        None,
+        amount_raw,
+        env.block.height,
+    )?;
+    write_total_supply_checkpoint(&mut deps.storage, total_supply, env.block.height)?;
+
+    store_redeem(
+        &mut deps.storage,
+        &sender_address,
+        amount,
+        native_denom.clone(),
+        &env.block,
+    )?;
+
+    let withdrawal_coins: Vec<Coin> = vec![Coin {
+        denom: native_denom,
+        amount,
+    }];
+
+    let res = HandleResponse {
+        messages: vec![CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: env.message.sender,
+            amount: withdrawal_coins,
+        })],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Redeem { status: Success })?),
+    };
+
+    Ok(res)
+}
+
+fn try_transfer_impl<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
     recipient: &HumanAddr,
     amount: Uint128,
+    memo: Option<String>,
 ) -> StdResult<()> {
     let sender_address = deps.api.canonical_address(&env.message.sender)?;
     let recipient_address = deps.api.canonical_address(recipient)?;
@@ -442,6 +963,7 @@ fn try_transfer_impl<S: Storage, A: Api, Q: Querier>(
         &sender_address,
         &recipient_address,
         amount.u128(),
+        env.block.height,
     )?;
 
     let symbol = Config::from_storage(&mut deps.storage).constants()?.symbol;
@@ -453,6 +975,8 @@ fn try_transfer_impl<S: Storage, A: Api, Q: Querier>(
         &recipient_address,
         amount,
         symbol,
+        memo,
+        &env.block,
     )?;
 
     Ok(())
@@ -463,8 +987,9 @@ fn try_transfer<S: Storage, A: Api, Q: Querier>(
     env: Env,
     recipient: &HumanAddr,
     amount: Uint128,
+    memo: Option<String>,
 ) -> StdResult<HandleResponse> {
-    try_transfer_impl(deps, env, recipient, amount)?;
+    try_transfer_impl(deps, &env, recipient, amount, memo)?;
 
     let res = HandleResponse {
         messages: vec![],
@@ -474,6 +999,24 @@ fn try_transfer<S: Storage, A: Api, Q: Querier>(
     Ok(res)
 }
 
+fn try_batch_transfer<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    actions: Vec<batch::TransferAction>,
+) -> StdResult<HandleResponse> {
+    for action in actions {
+        try_transfer_impl(deps, &env, &action.recipient, action.amount, action.memo)?;
+    }
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::BatchTransfer { status: Success })?),
+    };
+    Ok(res)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn try_add_receiver_api_callback<S: ReadonlyStorage>(
     messages: &mut Vec<CosmosMsg>,
     storage: &S,
@@ -482,11 +1025,12 @@ fn try_add_receiver_api_callback<S: ReadonlyStorage>(
     sender: HumanAddr,
     from: HumanAddr,
     amount: Uint128,
+    memo: Option<String>,
 ) -> StdResult<()> {
     let receiver_hash = get_receiver_hash(storage, recipient);
     if let Some(receiver_hash) = receiver_hash {
         let receiver_hash = receiver_hash?;
-        let receiver_msg = Snip20ReceiveMsg::new(sender, from, amount, msg);
+        let receiver_msg = Snip20ReceiveMsg::new(sender, from, amount, memo, msg);
         let callback_msg = receiver_msg.into_cosmos_msg(receiver_hash, recipient.clone())?;
 
         messages.push(callback_msg);
@@ -494,27 +1038,40 @@ fn try_add_receiver_api_callback<S: ReadonlyStorage>(
     Ok(())
 }
 
-fn try_send<S: Storage, A: Api, Q: Querier>(
+fn try_send_impl<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    env: Env,
+    env: &Env,
+    messages: &mut Vec<CosmosMsg>,
     recipient: &HumanAddr,
     amount: Uint128,
     msg: Option<Binary>,
-) -> StdResult<HandleResponse> {
-    let sender = env.message.sender.clone();
-    try_transfer_impl(deps, env, recipient, amount)?;
-
-    let mut messages = vec![];
+    memo: Option<String>,
+) -> StdResult<()> {
+    try_transfer_impl(deps, env, recipient, amount, memo.clone())?;
 
     try_add_receiver_api_callback(
-        &mut messages,
+        messages,
         &deps.storage,
         recipient,
         msg,
-        sender.clone(),
-        sender,
+        env.message.sender.clone(),
+        env.message.sender.clone(),
         amount,
-    )?;
+        memo,
+    )
+}
+
+fn try_send<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    recipient: &HumanAddr,
+    amount: Uint128,
+    msg: Option<Binary>,
+    memo: Option<String>,
+) -> StdResult<HandleResponse> {
+    let mut messages = vec![];
+
+    try_send_impl(deps, &env, &mut messages, recipient, amount, msg, memo)?;
 
     let res = HandleResponse {
         messages,
@@ -524,6 +1081,33 @@ fn try_send<S: Storage, A: Api, Q: Querier>(
     Ok(res)
 }
 
+fn try_batch_send<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    actions: Vec<batch::SendAction>,
+) -> StdResult<HandleResponse> {
+    let mut messages = vec![];
+
+    for action in actions {
+        try_send_impl(
+            deps,
+            &env,
+            &mut messages,
+            &action.recipient,
+            action.amount,
+            action.msg,
+            action.memo,
+        )?;
+    }
+
+    let res = HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::BatchSend { status: Success })?),
+    };
+    Ok(res)
+}
+
 fn try_register_receive<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -547,48 +1131,60 @@ fn insufficient_allowance(allowance: u128, required: u128) -> StdError {
     ))
 }
 
+/// Deducts `amount` from the allowance the owner gave the spender, failing if it's expired or
+/// too low
+fn use_allowance<S: Storage>(
+    storage: &mut S,
+    env: &Env,
+    owner: &CanonicalAddr,
+    spender: &CanonicalAddr,
+    amount: u128,
+) -> StdResult<()> {
+    let mut allowance = read_allowance(storage, owner, spender)?;
+
+    if allowance.expiration.map(|ex| ex < env.block.time) == Some(true) {
+        allowance.amount = 0;
+        write_allowance(storage, owner, spender, allowance)?;
+        return Err(insufficient_allowance(0, amount));
+    }
+
+    if let Some(new_allowance) = allowance.amount.checked_sub(amount) {
+        allowance.amount = new_allowance;
+    } else {
+        return Err(insufficient_allowance(allowance.amount, amount));
+    }
+
+    write_allowance(storage, owner, spender, allowance)?;
+
+    Ok(())
+}
+
 fn try_transfer_from_impl<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    env: Env,
+    env: &Env,
     owner: &HumanAddr,
     recipient: &HumanAddr,
     amount: Uint128,
+    memo: Option<String>,
 ) -> StdResult<()> {
     let spender_address = deps.api.canonical_address(&env.message.sender)?;
     let owner_address = deps.api.canonical_address(owner)?;
     let recipient_address = deps.api.canonical_address(recipient)?;
     let amount_raw = amount.u128();
 
-    let mut allowance = read_allowance(&deps.storage, &owner_address, &spender_address)?;
-
-    if allowance.expiration.map(|ex| ex < env.block.time) == Some(true) {
-        allowance.amount = 0;
-        write_allowance(
-            &mut deps.storage,
-            &owner_address,
-            &spender_address,
-            allowance,
-        )?;
-        return Err(insufficient_allowance(0, amount_raw));
-    }
-
-    if let Some(new_allowance) = allowance.amount.checked_sub(amount_raw) {
-        allowance.amount = new_allowance;
-    } else {
-        return Err(insufficient_allowance(allowance.amount, amount_raw));
-    }
-
-    write_allowance(
+    use_allowance(
         &mut deps.storage,
+        env,
         &owner_address,
         &spender_address,
-        allowance,
+        amount_raw,
     )?;
     perform_transfer(
         &mut deps.storage,
         &owner_address,
         &recipient_address,
         amount_raw,
+        env.block.height,
     )?;
 
     let symbol = Config::from_storage(&mut deps.storage).constants()?.symbol;
@@ -600,6 +1196,8 @@ fn try_transfer_from_impl<S: Storage, A: Api, Q: Querier>(
         &recipient_address,
         amount,
         symbol,
+        memo,
+        &env.block,
     )?;
 
     Ok(())
@@ -611,8 +1209,9 @@ fn try_transfer_from<S: Storage, A: Api, Q: Querier>(
     owner: &HumanAddr,
     recipient: &HumanAddr,
     amount: Uint128,
+    memo: Option<String>,
 ) -> StdResult<HandleResponse> {
-    try_transfer_from_impl(deps, env, owner, recipient, amount)?;
+    try_transfer_from_impl(deps, &env, owner, recipient, amount, memo)?;
 
     let res = HandleResponse {
         messages: vec![],
@@ -622,27 +1221,77 @@ fn try_transfer_from<S: Storage, A: Api, Q: Querier>(
     Ok(res)
 }
 
-fn try_send_from<S: Storage, A: Api, Q: Querier>(
+fn try_batch_transfer_from<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
+    actions: Vec<batch::TransferFromAction>,
+) -> StdResult<HandleResponse> {
+    for action in actions {
+        try_transfer_from_impl(
+            deps,
+            &env,
+            &action.owner,
+            &action.recipient,
+            action.amount,
+            action.memo,
+        )?;
+    }
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::BatchTransferFrom {
+            status: Success,
+        })?),
+    };
+    Ok(res)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn try_send_from_impl<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    messages: &mut Vec<CosmosMsg>,
     owner: &HumanAddr,
     recipient: &HumanAddr,
     amount: Uint128,
     msg: Option<Binary>,
-) -> StdResult<HandleResponse> {
-    let sender = env.message.sender.clone();
-    try_transfer_from_impl(deps, env, owner, recipient, amount)?;
-
-    let mut messages = vec![];
+    memo: Option<String>,
+) -> StdResult<()> {
+    try_transfer_from_impl(deps, env, owner, recipient, amount, memo.clone())?;
 
     try_add_receiver_api_callback(
-        &mut messages,
+        messages,
         &deps.storage,
         recipient,
         msg,
-        sender,
+        env.message.sender.clone(),
         owner.clone(),
         amount,
+        memo,
+    )
+}
+
+fn try_send_from<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    owner: &HumanAddr,
+    recipient: &HumanAddr,
+    amount: Uint128,
+    msg: Option<Binary>,
+    memo: Option<String>,
+) -> StdResult<HandleResponse> {
+    let mut messages = vec![];
+
+    try_send_from_impl(
+        deps,
+        &env,
+        &mut messages,
+        owner,
+        recipient,
+        amount,
+        msg,
+        memo,
     )?;
 
     let res = HandleResponse {
@@ -653,52 +1302,62 @@ fn try_send_from<S: Storage, A: Api, Q: Querier>(
     Ok(res)
 }
 
-fn try_burn_from<S: Storage, A: Api, Q: Querier>(
+fn try_batch_send_from<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    owner: &HumanAddr,
-    amount: Uint128,
+    actions: Vec<batch::SendFromAction>,
 ) -> StdResult<HandleResponse> {
-    let spender_address = deps.api.canonical_address(&env.message.sender)?;
-    let owner_address = deps.api.canonical_address(owner)?;
-    let amount = amount.u128();
-
-    let mut allowance = read_allowance(&deps.storage, &owner_address, &spender_address)?;
+    let mut messages = vec![];
 
-    if allowance.expiration.map(|ex| ex < env.block.time) == Some(true) {
-        allowance.amount = 0;
-        write_allowance(
-            &mut deps.storage,
-            &owner_address,
-            &spender_address,
-            allowance,
+    for action in actions {
+        try_send_from_impl(
+            deps,
+            &env,
+            &mut messages,
+            &action.owner,
+            &action.recipient,
+            action.amount,
+            action.msg,
+            action.memo,
         )?;
-        return Err(insufficient_allowance(0, amount));
     }
 
-    if let Some(new_allowance) = allowance.amount.checked_sub(amount) {
-        allowance.amount = new_allowance;
-    } else {
-        return Err(insufficient_allowance(allowance.amount, amount));
-    }
+    let res = HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::BatchSendFrom { status: Success })?),
+    };
+    Ok(res)
+}
+
+fn try_burn_from_impl<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    owner: &HumanAddr,
+    amount: Uint128,
+) -> StdResult<()> {
+    let spender_address = deps.api.canonical_address(&env.message.sender)?;
+    let owner_address = deps.api.canonical_address(owner)?;
+    let amount_raw = amount.u128();
 
-    write_allowance(
+    use_allowance(
         &mut deps.storage,
+        env,
         &owner_address,
         &spender_address,
-        allowance,
+        amount_raw,
     )?;
 
     // subtract from owner account
     let mut balances = Balances::from_storage(&mut deps.storage);
     let mut account_balance = balances.balance(&owner_address);
 
-    if let Some(new_balance) = account_balance.checked_sub(amount) {
+    if let Some(new_balance) = account_balance.checked_sub(amount_raw) {
         account_balance = new_balance;
     } else {
         return Err(StdError::generic_err(format!(
             "insufficient funds to burn: balance={}, required={}",
-            account_balance, amount
+            account_balance, amount_raw
         )));
     }
     balances.set_account_balance(&owner_address, account_balance);
@@ -706,7 +1365,7 @@ fn try_burn_from<S: Storage, A: Api, Q: Querier>(
     // remove from supply
     let mut config = Config::from_storage(&mut deps.storage);
     let mut total_supply = config.total_supply();
-    if let Some(new_total_supply) = total_supply.checked_sub(amount) {
+    if let Some(new_total_supply) = total_supply.checked_sub(amount_raw) {
         total_supply = new_total_supply;
     } else {
         return Err(StdError::generic_err(
@@ -715,6 +1374,36 @@ fn try_burn_from<S: Storage, A: Api, Q: Querier>(
     }
     config.set_total_supply(total_supply);
 
+    let symbol = config.constants()?.symbol;
+    store_burn(
+        &mut deps.storage,
+        &owner_address,
+        &spender_address,
+        amount,
+        symbol,
+        None,
+        &env.block,
+    )?;
+
+    let delegatee = read_delegate(&deps.storage, &owner_address)?;
+    move_voting_power(
+        &mut deps.storage,
+        delegatee.as_ref(),
+        None,
+        amount_raw,
+        env.block.height,
+    )?;
+    write_total_supply_checkpoint(&mut deps.storage, total_supply, env.block.height)
+}
+
+fn try_burn_from<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    owner: &HumanAddr,
+    amount: Uint128,
+) -> StdResult<HandleResponse> {
+    try_burn_from_impl(deps, &env, owner, amount)?;
+
     let res = HandleResponse {
         messages: vec![],
         log: vec![],
@@ -724,6 +1413,24 @@ fn try_burn_from<S: Storage, A: Api, Q: Querier>(
     Ok(res)
 }
 
+fn try_batch_burn_from<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    actions: Vec<batch::BurnFromAction>,
+) -> StdResult<HandleResponse> {
+    for action in actions {
+        try_burn_from_impl(deps, &env, &action.owner, action.amount)?;
+    }
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::BatchBurnFrom { status: Success })?),
+    };
+
+    Ok(res)
+}
+
 fn try_increase_allowance<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -848,6 +1555,76 @@ fn set_minters<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+fn try_delegate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    delegatee: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let owner = deps.api.canonical_address(&env.message.sender)?;
+    let delegatee = deps.api.canonical_address(&delegatee)?;
+
+    let previous = read_delegate(&deps.storage, &owner)?;
+    write_delegate(&mut deps.storage, &owner, &delegatee)?;
+
+    // Tokens only carry votes once they're delegated, so the whole balance moves over
+    let balance = ReadonlyBalances::from_storage(&deps.storage).account_amount(&owner);
+    move_voting_power(
+        &mut deps.storage,
+        previous.as_ref(),
+        Some(&delegatee),
+        balance,
+        env.block.height,
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Delegate { status: Success })?),
+    })
+}
+
+fn set_minter_quota<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    minter: HumanAddr,
+    quota: Option<MinterQuota>,
+) -> StdResult<HandleResponse> {
+    let config = Config::from_storage(&mut deps.storage);
+
+    check_if_admin(&config, &env.message.sender)?;
+
+    let minter = deps.api.canonical_address(&minter)?;
+    let quota = match quota {
+        Some(quota) => {
+            if quota.window == 0 {
+                return Err(StdError::generic_err("The quota window can't be empty"));
+            }
+
+            // Changing the quota doesn't reset what was already minted in the current window
+            let (window_start, minted) = read_minter_quota(&deps.storage, &minter)?
+                .map_or((env.block.height, 0), |current| {
+                    (current.window_start, current.minted)
+                });
+            Some(MinterQuotaState {
+                amount: quota.amount.u128(),
+                window: quota.window,
+                window_start,
+                minted,
+            })
+        }
+        None => None,
+    };
+    write_minter_quota(&mut deps.storage, &minter, quota.as_ref())?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetMinterQuota {
+            status: Success,
+        })?),
+    })
+}
+
 /// Burn tokens
 ///
 /// Remove `amount` tokens from the system irreversibly, from signer account
@@ -886,6 +1663,27 @@ fn try_burn<S: Storage, A: Api, Q: Querier>(
     }
     config.set_total_supply(total_supply);
 
+    let symbol = config.constants()?.symbol;
+    store_burn(
+        &mut deps.storage,
+        &sender_address,
+        &sender_address,
+        Uint128(amount),
+        symbol,
+        None,
+        &env.block,
+    )?;
+
+    let delegatee = read_delegate(&deps.storage, &sender_address)?;
+    move_voting_power(
+        &mut deps.storage,
+        delegatee.as_ref(),
+        None,
+        amount,
+        env.block.height,
+    )?;
+    write_total_supply_checkpoint(&mut deps.storage, total_supply, env.block.height)?;
+
     let res = HandleResponse {
         messages: vec![],
         log: vec![],
@@ -900,6 +1698,7 @@ fn perform_transfer<T: Storage>(
     from: &CanonicalAddr,
     to: &CanonicalAddr,
     amount: u128,
+    block: u64,
 ) -> StdResult<()> {
     let mut balances = Balances::from_storage(store);
 
@@ -920,7 +1719,15 @@ fn perform_transfer<T: Storage>(
     })?;
     balances.set_account_balance(to, to_balance);
 
-    Ok(())
+    let from_delegatee = read_delegate(store, from)?;
+    let to_delegatee = read_delegate(store, to)?;
+    move_voting_power(
+        store,
+        from_delegatee.as_ref(),
+        to_delegatee.as_ref(),
+        amount,
+        block,
+    )
 }
 
 fn is_admin<S: Storage>(config: &Config<S>, account: &HumanAddr) -> StdResult<bool> {
@@ -942,6 +1749,25 @@ fn check_if_admin<S: Storage>(config: &Config<S>, account: &HumanAddr) -> StdRes
     Ok(())
 }
 
+fn native_denom(constants: &Constants) -> StdResult<String> {
+    constants.native_denom.clone().ok_or_else(|| {
+        StdError::generic_err(
+            "This token doesn't wrap a native coin, deposits and redeems are disabled",
+        )
+    })
+}
+
+fn check_if_minter<S: ReadonlyStorage>(storage: &S, account: &HumanAddr) -> StdResult<()> {
+    let minters = ReadonlyConfig::from_storage(storage).minters();
+    if !minters.contains(account) {
+        return Err(StdError::generic_err(
+            "Minting is allowed to minter accounts only",
+        ));
+    }
+
+    Ok(())
+}
+
 fn is_valid_name(name: &str) -> bool {
     let len = name.len();
     3 <= len && len <= 30
@@ -967,8 +1793,10 @@ mod tests {
     use super::*;
     use crate::msg::ResponseStatus;
     use crate::msg::{InitConfig, InitialBalance};
+    use crate::state::TxAction;
     use cosmwasm_std::testing::*;
     use cosmwasm_std::{from_binary, BlockInfo, ContractInfo, MessageInfo, QueryResponse, WasmMsg};
+    use scrt_finance::permit::{PermitParams, PermitSignature, PubKey};
     use std::any::Any;
 
     // Helper functions
@@ -978,6 +1806,16 @@ mod tests {
     ) -> (
         StdResult<InitResponse>,
         Extern<MockStorage, MockApi, MockQuerier>,
+    ) {
+        init_helper_with_config(initial_balances, None)
+    }
+
+    fn init_helper_with_config(
+        initial_balances: Vec<InitialBalance>,
+        config: Option<&str>,
+    ) -> (
+        StdResult<InitResponse>,
+        Extern<MockStorage, MockApi, MockQuerier>,
     ) {
         let mut deps = mock_dependencies(20, &[]);
         let env = mock_env("instantiator", &[]);
@@ -989,7 +1827,7 @@ mod tests {
             decimals: 8,
             initial_balances: Some(initial_balances),
             prng_seed: Binary::from("lolz fun yay".as_bytes()),
-            config: None,
+            config: config.map(|config| from_binary(&Binary::from(config.as_bytes())).unwrap()),
         };
 
         (init(&mut deps, env, init_msg), deps)
@@ -1049,6 +1887,13 @@ mod tests {
             | HandleAnswer::Burn { status }
             | HandleAnswer::RegisterReceive { status }
             | HandleAnswer::SetViewingKey { status }
+            | HandleAnswer::RevokePermit { status }
+            | HandleAnswer::BatchTransfer { status }
+            | HandleAnswer::BatchSend { status }
+            | HandleAnswer::BatchTransferFrom { status }
+            | HandleAnswer::BatchSendFrom { status }
+            | HandleAnswer::BatchBurnFrom { status }
+            | HandleAnswer::BatchMint { status }
             | HandleAnswer::TransferFrom { status }
             | HandleAnswer::SendFrom { status }
             | HandleAnswer::BurnFrom { status }
@@ -1057,7 +1902,9 @@ mod tests {
             | HandleAnswer::SetContractStatus { status }
             | HandleAnswer::SetMinters { status }
             | HandleAnswer::AddMinters { status }
-            | HandleAnswer::RemoveMinters { status } => {
+            | HandleAnswer::RemoveMinters { status }
+            | HandleAnswer::SetMinterQuota { status }
+            | HandleAnswer::Delegate { status } => {
                 matches!(status, ResponseStatus::Success {..})
             }
             _ => panic!("HandleAnswer not supported for success extraction"),
@@ -1083,45 +1930,291 @@ mod tests {
         assert_eq!(constants.symbol, "SECSEC".to_string());
         assert_eq!(constants.decimals, 8);
         assert_eq!(
-            constants.prng_seed,
-            sha_256("lolz fun yay".to_owned().as_bytes())
+            constants.prng_seed,
+            sha_256("lolz fun yay".to_owned().as_bytes())
+        );
+        assert_eq!(constants.total_supply_is_public, false);
+    }
+
+    #[test]
+    fn test_total_supply_overflow() {
+        let (init_result, _deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("lebron".to_string()),
+            amount: Uint128(u128::max_value()),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let (init_result, _deps) = init_helper(vec![
+            InitialBalance {
+                address: HumanAddr("lebron".to_string()),
+                amount: Uint128(u128::max_value()),
+            },
+            InitialBalance {
+                address: HumanAddr("giannis".to_string()),
+                amount: Uint128(1),
+            },
+        ]);
+        let error = extract_error_msg(init_result);
+        assert_eq!(
+            error,
+            "The sum of all initial balances exceeds the maximum possible total supply"
+        );
+    }
+
+    // Handle tests
+
+    #[test]
+    fn test_handle_transfer() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(1000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result));
+        let bob_canonical = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let alice_canonical = deps
+            .api
+            .canonical_address(&HumanAddr("alice".to_string()))
+            .unwrap();
+        let balances = ReadonlyBalances::from_storage(&deps.storage);
+        assert_eq!(5000 - 1000, balances.account_amount(&bob_canonical));
+        assert_eq!(1000, balances.account_amount(&alice_canonical));
+
+        let handle_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(10000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient funds"));
+    }
+
+    #[test]
+    fn test_handle_batch_transfer() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::BatchTransfer {
+            actions: vec![
+                batch::TransferAction {
+                    recipient: HumanAddr("alice".to_string()),
+                    amount: Uint128(1000),
+                    memo: None,
+                },
+                batch::TransferAction {
+                    recipient: HumanAddr("charlie".to_string()),
+                    amount: Uint128(1500),
+                    memo: None,
+                },
+            ],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let balances = ReadonlyBalances::from_storage(&deps.storage);
+        for (name, amount) in &[("bob", 2500), ("alice", 1000), ("charlie", 1500)] {
+            let canonical = deps
+                .api
+                .canonical_address(&HumanAddr(name.to_string()))
+                .unwrap();
+            assert_eq!(*amount, balances.account_amount(&canonical));
+        }
+
+        // One history entry per action
+        let bob_canonical = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let (_, total) = get_transactions(&deps.api, &deps.storage, &bob_canonical, 0, 10).unwrap();
+        assert_eq!(total, 2);
+
+        // The whole batch fails if a single action does
+        let handle_msg = HandleMsg::BatchTransfer {
+            actions: vec![
+                batch::TransferAction {
+                    recipient: HumanAddr("alice".to_string()),
+                    amount: Uint128(1000),
+                    memo: None,
+                },
+                batch::TransferAction {
+                    recipient: HumanAddr("charlie".to_string()),
+                    amount: Uint128(10000),
+                    memo: None,
+                },
+            ],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient funds"));
+    }
+
+    #[test]
+    fn test_handle_batch_transfer_from_and_burn_from() {
+        let (init_result, mut deps) = init_helper(vec![
+            InitialBalance {
+                address: HumanAddr("bob".to_string()),
+                amount: Uint128(5000),
+            },
+            InitialBalance {
+                address: HumanAddr("jerry".to_string()),
+                amount: Uint128(5000),
+            },
+        ]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        for owner in &["bob", "jerry"] {
+            let handle_msg = HandleMsg::IncreaseAllowance {
+                spender: HumanAddr("alice".to_string()),
+                amount: Uint128(2000),
+                padding: None,
+                expiration: None,
+            };
+            let handle_result = handle(&mut deps, mock_env(*owner, &[]), handle_msg);
+            assert!(handle_result.is_ok());
+        }
+
+        let handle_msg = HandleMsg::BatchTransferFrom {
+            actions: vec![
+                batch::TransferFromAction {
+                    owner: HumanAddr("bob".to_string()),
+                    recipient: HumanAddr("charlie".to_string()),
+                    amount: Uint128(1000),
+                    memo: None,
+                },
+                batch::TransferFromAction {
+                    owner: HumanAddr("jerry".to_string()),
+                    recipient: HumanAddr("charlie".to_string()),
+                    amount: Uint128(1000),
+                    memo: None,
+                },
+            ],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::BatchBurnFrom {
+            actions: vec![
+                batch::BurnFromAction {
+                    owner: HumanAddr("bob".to_string()),
+                    amount: Uint128(500),
+                },
+                batch::BurnFromAction {
+                    owner: HumanAddr("jerry".to_string()),
+                    amount: Uint128(1000),
+                },
+            ],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let balances = ReadonlyBalances::from_storage(&deps.storage);
+        for (name, amount) in &[("bob", 3500), ("jerry", 3000), ("charlie", 2000)] {
+            let canonical = deps
+                .api
+                .canonical_address(&HumanAddr(name.to_string()))
+                .unwrap();
+            assert_eq!(*amount, balances.account_amount(&canonical));
+        }
+        assert_eq!(
+            ReadonlyConfig::from_storage(&deps.storage).total_supply(),
+            10000 - 1500
         );
-        assert_eq!(constants.total_supply_is_public, false);
+
+        // jerry's allowance is used up
+        let handle_msg = HandleMsg::BatchBurnFrom {
+            actions: vec![batch::BurnFromAction {
+                owner: HumanAddr("jerry".to_string()),
+                amount: Uint128(1),
+            }],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
     }
 
     #[test]
-    fn test_total_supply_overflow() {
-        let (init_result, _deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("lebron".to_string()),
-            amount: Uint128(u128::max_value()),
-        }]);
+    fn test_handle_batch_mint() {
+        let (init_result, mut deps) = init_helper(vec![]);
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
 
-        let (init_result, _deps) = init_helper(vec![
-            InitialBalance {
-                address: HumanAddr("lebron".to_string()),
-                amount: Uint128(u128::max_value()),
+        let actions = vec![
+            batch::MintAction {
+                recipient: HumanAddr("alice".to_string()),
+                amount: Uint128(100),
+                memo: None,
             },
-            InitialBalance {
-                address: HumanAddr("giannis".to_string()),
-                amount: Uint128(1),
+            batch::MintAction {
+                recipient: HumanAddr("bob".to_string()),
+                amount: Uint128(200),
+                memo: None,
             },
-        ]);
-        let error = extract_error_msg(init_result);
+        ];
+
+        let handle_msg = HandleMsg::BatchMint {
+            actions: actions.clone(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("allowed to minter accounts only"));
+
+        let handle_msg = HandleMsg::BatchMint {
+            actions,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
         assert_eq!(
-            error,
-            "The sum of all initial balances exceeds the maximum possible total supply"
+            ReadonlyConfig::from_storage(&deps.storage).total_supply(),
+            300
         );
     }
 
-    // Handle tests
-
     #[test]
-    fn test_handle_transfer() {
+    fn test_handle_send() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
             address: HumanAddr("bob".to_string()),
             amount: Uint128(5000),
@@ -1132,38 +2225,42 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        let handle_msg = HandleMsg::Transfer {
-            recipient: HumanAddr("alice".to_string()),
-            amount: Uint128(1000),
+        let handle_msg = HandleMsg::RegisterReceive {
+            code_hash: "this_is_a_hash_of_a_code".to_string(),
             padding: None,
         };
-        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let handle_result = handle(&mut deps, mock_env("contract", &[]), handle_msg);
         let result = handle_result.unwrap();
         assert!(ensure_success(result));
-        let bob_canonical = deps
-            .api
-            .canonical_address(&HumanAddr("bob".to_string()))
-            .unwrap();
-        let alice_canonical = deps
-            .api
-            .canonical_address(&HumanAddr("alice".to_string()))
-            .unwrap();
-        let balances = ReadonlyBalances::from_storage(&deps.storage);
-        assert_eq!(5000 - 1000, balances.account_amount(&bob_canonical));
-        assert_eq!(1000, balances.account_amount(&alice_canonical));
 
-        let handle_msg = HandleMsg::Transfer {
-            recipient: HumanAddr("alice".to_string()),
-            amount: Uint128(10000),
+        let handle_msg = HandleMsg::Send {
+            recipient: HumanAddr("contract".to_string()),
+            amount: Uint128(100),
+            memo: None,
             padding: None,
+            msg: Some(to_binary("hey hey you you").unwrap()),
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient funds"));
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result.clone()));
+        assert!(result.messages.contains(&CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: HumanAddr("contract".to_string()),
+            callback_code_hash: "this_is_a_hash_of_a_code".to_string(),
+            msg: Snip20ReceiveMsg::new(
+                HumanAddr("bob".to_string()),
+                HumanAddr("bob".to_string()),
+                Uint128(100),
+                None,
+                Some(to_binary("hey hey you you").unwrap())
+            )
+            .into_binary()
+            .unwrap(),
+            send: vec![]
+        })));
     }
 
     #[test]
-    fn test_handle_send() {
+    fn test_handle_send_with_memo() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
             address: HumanAddr("bob".to_string()),
             amount: Uint128(5000),
@@ -1179,14 +2276,14 @@ mod tests {
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("contract", &[]), handle_msg);
-        let result = handle_result.unwrap();
-        assert!(ensure_success(result));
+        assert!(ensure_success(handle_result.unwrap()));
 
         let handle_msg = HandleMsg::Send {
             recipient: HumanAddr("contract".to_string()),
             amount: Uint128(100),
+            msg: None,
+            memo: Some("invoice #42".to_string()),
             padding: None,
-            msg: Some(to_binary("hey hey you you").unwrap()),
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
         let result = handle_result.unwrap();
@@ -1198,12 +2295,46 @@ mod tests {
                 HumanAddr("bob".to_string()),
                 HumanAddr("bob".to_string()),
                 Uint128(100),
-                Some(to_binary("hey hey you you").unwrap())
+                Some("invoice #42".to_string()),
+                None,
             )
             .into_binary()
             .unwrap(),
             send: vec![]
         })));
+
+        let handle_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(200),
+            memo: Some("rent".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_msg = QueryMsg::TransferHistory {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let query_result = query(&deps, query_msg);
+        let transfers = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransferHistory { txs } => txs,
+            _ => panic!("Unexpected"),
+        };
+        let memos: Vec<Option<String>> = transfers.into_iter().map(|tx| tx.memo).collect();
+        assert_eq!(
+            memos,
+            vec![Some("rent".to_string()), Some("invoice #42".to_string())]
+        );
     }
 
     #[test]
@@ -1334,6 +2465,7 @@ mod tests {
             owner: HumanAddr("bob".to_string()),
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(2500),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1357,6 +2489,7 @@ mod tests {
             owner: HumanAddr("bob".to_string()),
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(2500),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1368,6 +2501,7 @@ mod tests {
             owner: HumanAddr("bob".to_string()),
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(2000),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(
@@ -1398,6 +2532,7 @@ mod tests {
             owner: HumanAddr("bob".to_string()),
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(2000),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1428,6 +2563,7 @@ mod tests {
             owner: HumanAddr("bob".to_string()),
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(1),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1453,6 +2589,7 @@ mod tests {
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(2500),
             msg: None,
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1477,6 +2614,7 @@ mod tests {
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(2500),
             msg: None,
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1499,6 +2637,7 @@ mod tests {
             HumanAddr("alice".to_string()),
             HumanAddr("bob".to_string()),
             Uint128(2000),
+            None,
             Some(send_msg.clone()),
         );
         let handle_msg = HandleMsg::SendFrom {
@@ -1506,6 +2645,7 @@ mod tests {
             recipient: HumanAddr("contract".to_string()),
             amount: Uint128(2000),
             msg: Some(send_msg),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1542,6 +2682,7 @@ mod tests {
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(1),
             msg: None,
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
@@ -1830,42 +2971,101 @@ mod tests {
 
     #[test]
     fn test_handle_redeem() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("butler".to_string()),
-            amount: Uint128(5000),
-        }]);
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: HumanAddr("butler".to_string()),
+                amount: Uint128(5000),
+            }],
+            Some(r#"{ "native_denom": "uscrt" }"#),
+        );
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
 
+        // Minted tokens have no native coin behind them
+        let handle_msg = HandleMsg::Redeem {
+            amount: Uint128(1000),
+            denom: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("butler", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient native reserve to redeem"));
+
+        let handle_msg = HandleMsg::Deposit { padding: None };
+        let handle_result = handle(
+            &mut deps,
+            mock_env(
+                "lebron",
+                &[Coin {
+                    denom: "uscrt".to_string(),
+                    amount: Uint128(600),
+                }],
+            ),
+            handle_msg,
+        );
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        // Butler can only take out what was deposited, not the rest of their minted balance
         let handle_msg = HandleMsg::Redeem {
             amount: Uint128(1000),
             denom: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("butler", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient native reserve to redeem"));
+
+        let handle_msg = HandleMsg::Redeem {
+            amount: Uint128(600),
+            denom: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("butler", &[]), handle_msg);
         assert!(
             handle_result.is_ok(),
             "handle() failed: {}",
             handle_result.err().unwrap()
         );
+        assert_eq!(
+            handle_result.unwrap().messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr(MOCK_CONTRACT_ADDR.to_string()),
+                to_address: HumanAddr("butler".to_string()),
+                amount: vec![Coin {
+                    denom: "uscrt".to_string(),
+                    amount: Uint128(600),
+                }],
+            })]
+        );
 
         let balances = ReadonlyBalances::from_storage(&deps.storage);
         let canonical = deps
             .api
             .canonical_address(&HumanAddr("butler".to_string()))
             .unwrap();
-        assert_eq!(balances.account_amount(&canonical), 4000)
+        assert_eq!(balances.account_amount(&canonical), 4400);
+        assert_eq!(
+            ReadonlyConfig::from_storage(&deps.storage).native_reserve(),
+            0
+        );
     }
 
     #[test]
     fn test_handle_deposit() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("lebron".to_string()),
-            amount: Uint128(5000),
-        }]);
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: HumanAddr("lebron".to_string()),
+                amount: Uint128(5000),
+            }],
+            Some(r#"{ "native_denom": "uscrt" }"#),
+        );
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
@@ -1898,6 +3098,51 @@ mod tests {
         assert_eq!(balances.account_amount(&canonical), 6000)
     }
 
+    #[test]
+    fn test_handle_deposit_and_redeem_without_native_denom() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("lebron".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::Deposit { padding: None };
+        let handle_result = handle(
+            &mut deps,
+            mock_env(
+                "lebron",
+                &[Coin {
+                    denom: "uscrt".to_string(),
+                    amount: Uint128(1000),
+                }],
+            ),
+            handle_msg,
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("deposits and redeems are disabled"));
+
+        let handle_msg = HandleMsg::Redeem {
+            amount: Uint128(1000),
+            denom: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("deposits and redeems are disabled"));
+
+        match from_binary(&query(&deps, QueryMsg::ExchangeRate {}).unwrap()).unwrap() {
+            QueryAnswer::ExchangeRate { rate, denom } => {
+                assert_eq!(rate, Uint128(0));
+                assert_eq!(denom, "");
+            }
+            _ => panic!("Unexpected"),
+        }
+    }
+
     #[test]
     fn test_handle_burn() {
         let initial_amount: u128 = 5000;
@@ -1946,6 +3191,7 @@ mod tests {
         let handle_msg = HandleMsg::Mint {
             recipient: HumanAddr("lebron".to_string()),
             amount: Uint128(mint_amount),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
@@ -1955,8 +3201,100 @@ mod tests {
             handle_result.err().unwrap()
         );
 
-        let new_supply = ReadonlyConfig::from_storage(&deps.storage).total_supply();
-        assert_eq!(new_supply, supply + mint_amount);
+        let new_supply = ReadonlyConfig::from_storage(&deps.storage).total_supply();
+        assert_eq!(new_supply, supply + mint_amount);
+    }
+
+    #[test]
+    fn test_handle_mint_with_max_supply_and_quota() {
+        let mut deps = mock_dependencies(20, &[]);
+        let init_config: InitConfig =
+            from_binary(&Binary::from(r#"{ "max_supply": "10000" }"#.as_bytes())).unwrap();
+        let init_msg = InitMsg {
+            name: "sec-sec".to_string(),
+            admin: Some(HumanAddr("admin".to_string())),
+            symbol: "SECSEC".to_string(),
+            decimals: 8,
+            initial_balances: Some(vec![InitialBalance {
+                address: HumanAddr("lebron".to_string()),
+                amount: Uint128(5000),
+            }]),
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            config: Some(init_config),
+        };
+        let init_result = init(&mut deps, mock_env("instantiator", &[]), init_msg);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let env_at = |sender: &str, height: u64| {
+            let mut env = mock_env(sender, &[]);
+            env.block.height = height;
+            env
+        };
+        let mint_msg = |amount: u128| HandleMsg::Mint {
+            recipient: HumanAddr("lebron".to_string()),
+            amount: Uint128(amount),
+            memo: None,
+            padding: None,
+        };
+
+        let set_quota_msg = || HandleMsg::SetMinterQuota {
+            minter: HumanAddr("admin".to_string()),
+            quota: Some(MinterQuota {
+                amount: Uint128(1000),
+                window: 100,
+            }),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("lebron", 1000), set_quota_msg());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
+        let handle_result = handle(&mut deps, env_at("admin", 1000), set_quota_msg());
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_result = handle(&mut deps, env_at("admin", 1000), mint_msg(600));
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = handle(&mut deps, env_at("admin", 1099), mint_msg(600));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("exceed the minter's quota of 1000 per 100 blocks"));
+
+        let query_msg = QueryMsg::MinterQuota {
+            minter: HumanAddr("admin".to_string()),
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::MinterQuota {
+                window_start,
+                minted_in_window,
+                ..
+            } => {
+                assert_eq!(window_start, Some(1000));
+                assert_eq!(minted_in_window, Uint128(600));
+            }
+            _ => panic!("Unexpected"),
+        }
+
+        // A new window starts, but the max supply still applies
+        for height in &[1100, 1200, 1300, 1400] {
+            let handle_result = handle(&mut deps, env_at("admin", *height), mint_msg(1000));
+            assert!(ensure_success(handle_result.unwrap()));
+        }
+        let handle_result = handle(&mut deps, env_at("admin", 1500), mint_msg(401));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("above the max supply of 10000"));
+        let handle_result = handle(&mut deps, env_at("admin", 1500), mint_msg(400));
+        assert!(ensure_success(handle_result.unwrap()));
+        assert_eq!(
+            ReadonlyConfig::from_storage(&deps.storage).total_supply(),
+            10000
+        );
+
+        match from_binary(&query(&deps, QueryMsg::MaxSupply {}).unwrap()).unwrap() {
+            QueryAnswer::MaxSupply { max_supply } => assert_eq!(max_supply, Some(Uint128(10000))),
+            _ => panic!("Unexpected"),
+        }
     }
 
     #[test]
@@ -2016,16 +3354,37 @@ mod tests {
 
     #[test]
     fn test_handle_pause_with_withdrawals() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("lebron".to_string()),
-            amount: Uint128(5000),
-        }]);
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: HumanAddr("lebron".to_string()),
+                amount: Uint128(5000),
+            }],
+            Some(r#"{ "native_denom": "uscrt" }"#),
+        );
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
 
+        let deposit_msg = HandleMsg::Deposit { padding: None };
+        let handle_result = handle(
+            &mut deps,
+            mock_env(
+                "lebron",
+                &[Coin {
+                    denom: "uscrt".to_string(),
+                    amount: Uint128(5000),
+                }],
+            ),
+            deposit_msg,
+        );
+        assert!(
+            handle_result.is_ok(),
+            "Deposit failed: {}",
+            handle_result.err().unwrap()
+        );
+
         let pause_msg = HandleMsg::SetContractStatus {
             level: ContractStatusLevel::StopAllButRedeems,
             padding: None,
@@ -2041,6 +3400,7 @@ mod tests {
         let send_msg = HandleMsg::Transfer {
             recipient: HumanAddr("account".to_string()),
             amount: Uint128(123),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("admin", &[]), send_msg);
@@ -2090,6 +3450,7 @@ mod tests {
         let send_msg = HandleMsg::Transfer {
             recipient: HumanAddr("account".to_string()),
             amount: Uint128(123),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("admin", &[]), send_msg);
@@ -2142,6 +3503,7 @@ mod tests {
         let handle_msg = HandleMsg::Mint {
             recipient: HumanAddr("bob".to_string()),
             amount: Uint128(100),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
@@ -2150,6 +3512,7 @@ mod tests {
         let handle_msg = HandleMsg::Mint {
             recipient: HumanAddr("bob".to_string()),
             amount: Uint128(100),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
@@ -2187,6 +3550,7 @@ mod tests {
         let handle_msg = HandleMsg::Mint {
             recipient: HumanAddr("bob".to_string()),
             amount: Uint128(100),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
@@ -2195,6 +3559,7 @@ mod tests {
         let handle_msg = HandleMsg::Mint {
             recipient: HumanAddr("bob".to_string()),
             amount: Uint128(100),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
@@ -2231,6 +3596,7 @@ mod tests {
         let handle_msg = HandleMsg::Mint {
             recipient: HumanAddr("bob".to_string()),
             amount: Uint128(100),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
@@ -2240,6 +3606,7 @@ mod tests {
         let handle_msg = HandleMsg::Mint {
             recipient: HumanAddr("bob".to_string()),
             amount: Uint128(100),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
@@ -2257,6 +3624,7 @@ mod tests {
         let handle_msg = HandleMsg::Mint {
             recipient: HumanAddr("bob".to_string()),
             amount: Uint128(100),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
@@ -2266,6 +3634,7 @@ mod tests {
         let handle_msg = HandleMsg::Mint {
             recipient: HumanAddr("bob".to_string()),
             amount: Uint128(100),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
@@ -2498,6 +3867,209 @@ mod tests {
         assert_eq!(allowance, Uint128(0));
     }
 
+    #[test]
+    fn test_query_allowances_given_and_received() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        for account in &["bob", "alice"] {
+            let handle_msg = HandleMsg::SetViewingKey {
+                key: "key".to_string(),
+                padding: None,
+            };
+            let handle_result = handle(&mut deps, mock_env(*account, &[]), handle_msg);
+            assert!(ensure_success(handle_result.unwrap()));
+        }
+
+        for (owner, spender, amount) in &[
+            ("bob", "alice", 100),
+            ("bob", "charlie", 200),
+            ("jerry", "alice", 50),
+        ] {
+            let handle_msg = HandleMsg::IncreaseAllowance {
+                spender: HumanAddr(spender.to_string()),
+                amount: Uint128(*amount),
+                padding: None,
+                expiration: Some(1_571_797_500),
+            };
+            let handle_result = handle(&mut deps, mock_env(*owner, &[]), handle_msg);
+            assert!(handle_result.is_ok());
+        }
+
+        // Allowances that went down to zero aren't listed
+        let handle_msg = HandleMsg::DecreaseAllowance {
+            spender: HumanAddr("charlie".to_string()),
+            amount: Uint128(200),
+            padding: None,
+            expiration: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(handle_result.is_ok());
+
+        let query_msg = QueryMsg::AllowancesGiven {
+            owner: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::AllowancesGiven {
+                allowances, count, ..
+            } => {
+                // Charlie is still counted, only the page itself is filtered
+                assert_eq!(count, 2);
+                assert_eq!(
+                    allowances,
+                    vec![AllowanceGiven {
+                        spender: HumanAddr("alice".to_string()),
+                        allowance: Uint128(100),
+                        expiration: Some(1_571_797_500),
+                    }]
+                );
+            }
+            _ => panic!("Unexpected"),
+        };
+
+        let query_msg = QueryMsg::AllowancesReceived {
+            spender: HumanAddr("alice".to_string()),
+            key: "key".to_string(),
+            page: Some(1),
+            page_size: 1,
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::AllowancesReceived {
+                allowances, count, ..
+            } => {
+                assert_eq!(count, 2);
+                assert_eq!(
+                    allowances,
+                    vec![AllowanceReceived {
+                        owner: HumanAddr("jerry".to_string()),
+                        allowance: Uint128(50),
+                        expiration: Some(1_571_797_500),
+                    }]
+                );
+            }
+            _ => panic!("Unexpected"),
+        };
+
+        let query_msg = QueryMsg::AllowancesReceived {
+            spender: HumanAddr("alice".to_string()),
+            key: "key".to_string(),
+            page: Some(u32::MAX),
+            page_size: u32::MAX,
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::AllowancesReceived { allowances, .. } => assert!(allowances.is_empty()),
+            _ => panic!("Unexpected"),
+        };
+
+        // The viewing key must belong to the owner
+        let query_msg = QueryMsg::AllowancesGiven {
+            owner: HumanAddr("jerry".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("Wrong viewing key"));
+    }
+
+    #[test]
+    fn test_query_voting_power() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let env_at = |sender: &str, height: u64| {
+            let mut env = mock_env(sender, &[]);
+            env.block.height = height;
+            env
+        };
+
+        let handle_msg = HandleMsg::Delegate {
+            delegatee: HumanAddr("alice".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("bob", 20_000), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        // Tokens moving to an account without a delegate stop counting as votes
+        let handle_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("charlie".to_string()),
+            amount: Uint128(1000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("bob", 20_010), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::Delegate {
+            delegatee: HumanAddr("charlie".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("bob", 20_020), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        for (block, expected) in &[
+            (Some(19_999), 0),
+            (Some(20_000), 5000),
+            (Some(20_015), 4000),
+            (Some(20_020), 0),
+            (None, 0),
+        ] {
+            let query_msg = QueryMsg::VotingPower {
+                address: HumanAddr("alice".to_string()),
+                key: "key".to_string(),
+                block: *block,
+            };
+            match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+                QueryAnswer::VotingPower { voting_power } => {
+                    assert_eq!(voting_power, Uint128(*expected), "block {:?}", block)
+                }
+                _ => panic!("Unexpected"),
+            }
+        }
+
+        let charlie = deps
+            .api
+            .canonical_address(&HumanAddr("charlie".to_string()))
+            .unwrap();
+        assert_eq!(
+            get_voting_power(&deps.storage, &charlie, None).unwrap(),
+            4000
+        );
+
+        // The total supply isn't public in this config
+        let query_msg = QueryMsg::TotalVotingPowerAt { block: 20_000 };
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("The total supply of this token is private"));
+        assert_eq!(
+            get_total_voting_power(&deps.storage, Some(20_000)).unwrap(),
+            5000
+        );
+    }
+
     #[test]
     fn test_query_balance() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
@@ -2545,6 +4117,48 @@ mod tests {
         assert_eq!(balance, Uint128(5000));
     }
 
+    #[test]
+    fn test_query_responses_are_padded() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let queries = vec![
+            QueryMsg::TokenInfo {},
+            QueryMsg::Balance {
+                address: HumanAddr("bob".to_string()),
+                key: "key".to_string(),
+            },
+            QueryMsg::Balance {
+                address: HumanAddr("bob".to_string()),
+                key: "wrong_key".to_string(),
+            },
+            QueryMsg::TransferHistory {
+                address: HumanAddr("bob".to_string()),
+                key: "key".to_string(),
+                page: None,
+                page_size: 10,
+            },
+        ];
+        for query_msg in queries {
+            let response = query(&deps, query_msg).unwrap();
+            assert_eq!(response.len() % RESPONSE_BLOCK_SIZE, 0);
+        }
+    }
+
     #[test]
     fn test_query_transfer_history() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
@@ -2567,6 +4181,7 @@ mod tests {
         let handle_msg = HandleMsg::Transfer {
             recipient: HumanAddr("alice".to_string()),
             amount: Uint128(1000),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
@@ -2575,6 +4190,7 @@ mod tests {
         let handle_msg = HandleMsg::Transfer {
             recipient: HumanAddr("banana".to_string()),
             amount: Uint128(500),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
@@ -2583,6 +4199,7 @@ mod tests {
         let handle_msg = HandleMsg::Transfer {
             recipient: HumanAddr("mango".to_string()),
             amount: Uint128(2500),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
@@ -2630,4 +4247,202 @@ mod tests {
         };
         assert_eq!(transfers.len(), 2);
     }
+
+    #[test]
+    fn test_query_transaction_history() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(1000),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::Burn {
+            amount: Uint128(500),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::Mint {
+            recipient: HumanAddr("bob".to_string()),
+            amount: Uint128(100),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_msg = QueryMsg::TransactionHistory {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let query_result = query(&deps, query_msg);
+        let (txs, total) = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransactionHistory { txs, total } => (txs, total),
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(total, Some(3));
+
+        let env = mock_env("bob", &[]);
+        let expected_actions = vec![
+            TxAction::Mint {
+                minter: HumanAddr("admin".to_string()),
+                recipient: HumanAddr("bob".to_string()),
+            },
+            TxAction::Burn {
+                burner: HumanAddr("bob".to_string()),
+                owner: HumanAddr("bob".to_string()),
+            },
+            TxAction::Transfer {
+                from: HumanAddr("bob".to_string()),
+                sender: HumanAddr("bob".to_string()),
+                recipient: HumanAddr("alice".to_string()),
+            },
+        ];
+        let expected_amounts = vec![100, 500, 1000];
+        for ((tx, action), amount) in txs.iter().zip(expected_actions).zip(expected_amounts) {
+            assert_eq!(tx.action, action);
+            assert_eq!(tx.coins.amount, Uint128(amount));
+            assert_eq!(tx.coins.denom, "SECSEC");
+            assert_eq!(tx.block_height, env.block.height);
+            assert_eq!(tx.block_time, env.block.time);
+        }
+
+        // The legacy query only sees the transfer
+        let query_msg = QueryMsg::TransferHistory {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let query_result = query(&deps, query_msg);
+        let transfers = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransferHistory { txs } => txs,
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].receiver, HumanAddr("alice".to_string()));
+        assert_eq!(transfers[0].block_height, Some(env.block.height));
+
+        // Pages only count transfers
+        for (page, page_size) in vec![(1, 1), (u32::MAX, u32::MAX)] {
+            let query_msg = QueryMsg::TransferHistory {
+                address: HumanAddr("bob".to_string()),
+                key: "key".to_string(),
+                page: Some(page),
+                page_size,
+            };
+            match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+                QueryAnswer::TransferHistory { txs } => assert!(txs.is_empty()),
+                _ => panic!("Unexpected"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_query_with_permit() {
+        let signer = HumanAddr("secret1wv66lgmt6azsngtdcqrkgyt7hjsk3r9lkvzqcx".to_string());
+        // Signer addresses are longer than the usual mock ones
+        let mut deps = mock_dependencies(45, &[]);
+        let init_msg = InitMsg {
+            name: "sec-sec".to_string(),
+            admin: Some(HumanAddr("admin".to_string())),
+            symbol: "SECSEC".to_string(),
+            decimals: 8,
+            initial_balances: Some(vec![InitialBalance {
+                address: signer.clone(),
+                amount: Uint128(5000),
+            }]),
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            config: None,
+        };
+        init(&mut deps, mock_env("instantiator", &[]), init_msg).unwrap();
+
+        // Signed for this contract, with the `balance` permission only
+        let permit = Permit {
+            params: PermitParams {
+                allowed_tokens: vec![HumanAddr(MOCK_CONTRACT_ADDR.to_string())],
+                permit_name: "test".to_string(),
+                chain_id: "secret-4".to_string(),
+                permissions: vec![Permission::Balance],
+            },
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary::from_base64("Aq5U7xmLb4QZjttvvflKkSN8wD9ywJ8brS/IhFKlww5F")
+                        .unwrap(),
+                },
+                signature: Binary::from_base64(
+                    "q+oy5eoKpsf+shFw54rqYrc5iCnWNV1AQzQq3D1z7wshXl7gr5Qrar9l+pOdGBHk+a66+5bj64WhB9bthpH1ug==",
+                )
+                .unwrap(),
+            },
+        };
+
+        let query_msg = QueryMsg::WithPermit {
+            permit: permit.clone(),
+            query: QueryWithPermit::Balance {},
+        };
+        let balance = match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::Balance { amount } => amount,
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(balance, Uint128(5000));
+
+        let query_msg = QueryMsg::WithPermit {
+            permit: permit.clone(),
+            query: QueryWithPermit::TransferHistory {
+                page: None,
+                page_size: 10,
+            },
+        };
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("No permission to query history"));
+
+        let query_msg = QueryMsg::WithPermit {
+            permit: permit.clone(),
+            query: QueryWithPermit::Allowance {
+                owner: HumanAddr("alice".to_string()),
+                spender: HumanAddr("bob".to_string()),
+            },
+        };
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("No permission to query allowance"));
+
+        let handle_msg = HandleMsg::RevokePermit {
+            permit_name: "test".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env(signer.0.as_str(), &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_msg = QueryMsg::WithPermit {
+            permit,
+            query: QueryWithPermit::Balance {},
+        };
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("was revoked"));
+    }
 }
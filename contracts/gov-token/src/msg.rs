@@ -3,8 +3,10 @@ use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{Binary, HumanAddr, StdError, StdResult, Uint128};
 
-use crate::state::Tx;
+use crate::batch;
+use crate::state::{RichTx, Tx};
 use crate::viewing_key::ViewingKey;
+use scrt_finance::permit::Permit;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct InitialBalance {
@@ -38,27 +40,54 @@ pub struct InitConfig {
     /// Indicates whether the total supply is public or should be kept secret.
     /// default: False
     public_total_supply: Option<bool>,
+    /// The total supply can never be minted above this. It can't be changed after init.
+    /// default: None (only limited by the u128 range)
+    max_supply: Option<Uint128>,
+    /// The native coin this token wraps 1:1, e.g. "uscrt". Deposits and redeems are only
+    /// enabled when it's set.
+    /// default: None
+    native_denom: Option<String>,
 }
 
 impl InitConfig {
     pub fn public_total_supply(&self) -> bool {
         self.public_total_supply.unwrap_or(false)
     }
+
+    pub fn max_supply(&self) -> Option<u128> {
+        self.max_supply.map(|max_supply| max_supply.u128())
+    }
+
+    pub fn native_denom(&self) -> Option<String> {
+        self.native_denom.clone()
+    }
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleMsg {
+    // Native coin interactions
+    Redeem {
+        amount: Uint128,
+        denom: Option<String>,
+        padding: Option<String>,
+    },
+    Deposit {
+        padding: Option<String>,
+    },
+
     // Base ERC-20 stuff
     Transfer {
         recipient: HumanAddr,
         amount: Uint128,
+        memo: Option<String>,
         padding: Option<String>,
     },
     Send {
         recipient: HumanAddr,
         amount: Uint128,
         msg: Option<Binary>,
+        memo: Option<String>,
         padding: Option<String>,
     },
     Burn {
@@ -77,6 +106,10 @@ pub enum HandleMsg {
         key: String,
         padding: Option<String>,
     },
+    RevokePermit {
+        permit_name: String,
+        padding: Option<String>,
+    },
 
     // Allowance
     IncreaseAllowance {
@@ -95,6 +128,7 @@ pub enum HandleMsg {
         owner: HumanAddr,
         recipient: HumanAddr,
         amount: Uint128,
+        memo: Option<String>,
         padding: Option<String>,
     },
     SendFrom {
@@ -102,6 +136,7 @@ pub enum HandleMsg {
         recipient: HumanAddr,
         amount: Uint128,
         msg: Option<Binary>,
+        memo: Option<String>,
         padding: Option<String>,
     },
     BurnFrom {
@@ -110,10 +145,37 @@ pub enum HandleMsg {
         padding: Option<String>,
     },
 
+    // Batch
+    BatchTransfer {
+        actions: Vec<batch::TransferAction>,
+        padding: Option<String>,
+    },
+    BatchSend {
+        actions: Vec<batch::SendAction>,
+        padding: Option<String>,
+    },
+    BatchTransferFrom {
+        actions: Vec<batch::TransferFromAction>,
+        padding: Option<String>,
+    },
+    BatchSendFrom {
+        actions: Vec<batch::SendFromAction>,
+        padding: Option<String>,
+    },
+    BatchBurnFrom {
+        actions: Vec<batch::BurnFromAction>,
+        padding: Option<String>,
+    },
+    BatchMint {
+        actions: Vec<batch::MintAction>,
+        padding: Option<String>,
+    },
+
     // Mint
     Mint {
         recipient: HumanAddr,
         amount: Uint128,
+        memo: Option<String>,
         padding: Option<String>,
     },
     AddMinters {
@@ -128,6 +190,18 @@ pub enum HandleMsg {
         minters: Vec<HumanAddr>,
         padding: Option<String>,
     },
+    /// `None` removes the minter's quota
+    SetMinterQuota {
+        minter: HumanAddr,
+        quota: Option<MinterQuota>,
+        padding: Option<String>,
+    },
+
+    // Governance
+    Delegate {
+        delegatee: HumanAddr,
+        padding: Option<String>,
+    },
 
     // Admin
     ChangeAdmin {
@@ -170,6 +244,9 @@ pub enum HandleAnswer {
     SetViewingKey {
         status: ResponseStatus,
     },
+    RevokePermit {
+        status: ResponseStatus,
+    },
 
     // Allowance
     IncreaseAllowance {
@@ -192,6 +269,26 @@ pub enum HandleAnswer {
         status: ResponseStatus,
     },
 
+    // Batch
+    BatchTransfer {
+        status: ResponseStatus,
+    },
+    BatchSend {
+        status: ResponseStatus,
+    },
+    BatchTransferFrom {
+        status: ResponseStatus,
+    },
+    BatchSendFrom {
+        status: ResponseStatus,
+    },
+    BatchBurnFrom {
+        status: ResponseStatus,
+    },
+    BatchMint {
+        status: ResponseStatus,
+    },
+
     // Mint
     Mint {
         status: ResponseStatus,
@@ -205,6 +302,14 @@ pub enum HandleAnswer {
     SetMinters {
         status: ResponseStatus,
     },
+    SetMinterQuota {
+        status: ResponseStatus,
+    },
+
+    // Governance
+    Delegate {
+        status: ResponseStatus,
+    },
 
     // Other
     ChangeAdmin {
@@ -235,7 +340,42 @@ pub enum QueryMsg {
         page: Option<u32>,
         page_size: u32,
     },
+    TransactionHistory {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    AllowancesGiven {
+        owner: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    AllowancesReceived {
+        spender: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    /// Votes delegated to the address at the end of `block`, or currently if it's not set
+    VotingPower {
+        address: HumanAddr,
+        key: String,
+        block: Option<u64>,
+    },
+    TotalVotingPowerAt {
+        block: u64,
+    },
     Minters {},
+    MaxSupply {},
+    MinterQuota {
+        minter: HumanAddr,
+    },
+    WithPermit {
+        permit: Permit,
+        query: QueryWithPermit,
+    },
 }
 
 impl QueryMsg {
@@ -243,6 +383,14 @@ impl QueryMsg {
         match self {
             Self::Balance { address, key } => (vec![address], ViewingKey(key.clone())),
             Self::TransferHistory { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            Self::TransactionHistory { address, key, .. } => {
+                (vec![address], ViewingKey(key.clone()))
+            }
+            Self::AllowancesGiven { owner, key, .. } => (vec![owner], ViewingKey(key.clone())),
+            Self::AllowancesReceived { spender, key, .. } => {
+                (vec![spender], ViewingKey(key.clone()))
+            }
+            Self::VotingPower { address, key, .. } => (vec![address], ViewingKey(key.clone())),
             Self::Allowance {
                 owner,
                 spender,
@@ -254,6 +402,37 @@ impl QueryMsg {
     }
 }
 
+/// Authenticated queries that can be made with a SNIP-24 permit instead of a viewing key. The
+/// address is the one that signed the permit
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryWithPermit {
+    Allowance {
+        owner: HumanAddr,
+        spender: HumanAddr,
+    },
+    Balance {},
+    TransferHistory {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    TransactionHistory {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    AllowancesGiven {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    AllowancesReceived {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    VotingPower {
+        block: Option<u64>,
+    },
+}
+
 #[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryAnswer {
@@ -279,6 +458,26 @@ pub enum QueryAnswer {
     TransferHistory {
         txs: Vec<Tx>,
     },
+    TransactionHistory {
+        txs: Vec<RichTx>,
+        total: Option<u64>,
+    },
+    AllowancesGiven {
+        owner: HumanAddr,
+        allowances: Vec<AllowanceGiven>,
+        count: u32,
+    },
+    AllowancesReceived {
+        spender: HumanAddr,
+        allowances: Vec<AllowanceReceived>,
+        count: u32,
+    },
+    VotingPower {
+        voting_power: Uint128,
+    },
+    TotalVotingPowerAt {
+        total_voting_power: Uint128,
+    },
 
     ViewingKeyError {
         msg: String,
@@ -286,6 +485,36 @@ pub enum QueryAnswer {
     Minters {
         minters: Vec<HumanAddr>,
     },
+    MaxSupply {
+        max_supply: Option<Uint128>,
+    },
+    MinterQuota {
+        quota: Option<MinterQuota>,
+        /// Block the current window started at
+        window_start: Option<u64>,
+        minted_in_window: Uint128,
+    },
+}
+
+/// Caps how much a minter can mint in each window of `window` blocks
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MinterQuota {
+    pub amount: Uint128,
+    pub window: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowanceGiven {
+    pub spender: HumanAddr,
+    pub allowance: Uint128,
+    pub expiration: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowanceReceived {
+    pub owner: HumanAddr,
+    pub allowance: Uint128,
+    pub expiration: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
@@ -325,20 +554,6 @@ pub fn u8_to_status_level(status_level: u8) -> StdResult<ContractStatusLevel> {
     }
 }
 
-// Take a Vec<u8> and pad it up to a multiple of `block_size`, using spaces at the end.
-pub fn space_pad(block_size: usize, message: &mut Vec<u8>) -> &mut Vec<u8> {
-    let len = message.len();
-    let surplus = len % block_size;
-    if surplus == 0 {
-        return message;
-    }
-
-    let missing = block_size - surplus;
-    message.reserve(missing);
-    message.extend(std::iter::repeat(b' ').take(missing));
-    message
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -3,7 +3,8 @@ use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{to_binary, Binary, CosmosMsg, HumanAddr, StdResult, Uint128, WasmMsg};
 
-use crate::{contract::RESPONSE_BLOCK_SIZE, msg::space_pad};
+use crate::contract::RESPONSE_BLOCK_SIZE;
+use scrt_finance::padding::space_pad;
 
 /// Snip20ReceiveMsg should be de/serialized under `Receive()` variant in a HandleMsg
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -12,15 +13,24 @@ pub struct Snip20ReceiveMsg {
     pub sender: HumanAddr,
     pub from: HumanAddr,
     pub amount: Uint128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
     pub msg: Option<Binary>,
 }
 
 impl Snip20ReceiveMsg {
-    pub fn new(sender: HumanAddr, from: HumanAddr, amount: Uint128, msg: Option<Binary>) -> Self {
+    pub fn new(
+        sender: HumanAddr,
+        from: HumanAddr,
+        amount: Uint128,
+        memo: Option<String>,
+        msg: Option<Binary>,
+    ) -> Self {
         Self {
             sender,
             from,
             amount,
+            memo,
             msg,
         }
     }
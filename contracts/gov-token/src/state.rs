@@ -2,7 +2,8 @@ use std::any::type_name;
 use std::convert::TryFrom;
 
 use cosmwasm_std::{
-    Api, CanonicalAddr, Coin, HumanAddr, ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+    Api, BlockInfo, CanonicalAddr, Coin, HumanAddr, ReadonlyStorage, StdError, StdResult, Storage,
+    Uint128,
 };
 use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
 
@@ -16,101 +17,296 @@ use crate::viewing_key::ViewingKey;
 use serde::de::DeserializeOwned;
 
 pub static CONFIG_KEY: &[u8] = b"config";
-pub const PREFIX_TXS: &[u8] = b"transfers";
+pub const PREFIX_TXS: &[u8] = b"transactions";
+pub const PREFIX_TRANSFERS: &[u8] = b"transfers";
 
 pub const KEY_CONSTANTS: &[u8] = b"constants";
 pub const KEY_TOTAL_SUPPLY: &[u8] = b"total_supply";
 pub const KEY_CONTRACT_STATUS: &[u8] = b"contract_status";
 pub const KEY_MINTERS: &[u8] = b"minters";
 pub const KEY_TX_COUNT: &[u8] = b"tx-count";
+pub const KEY_NATIVE_RESERVE: &[u8] = b"native-reserve";
 
 pub const PREFIX_CONFIG: &[u8] = b"config";
 pub const PREFIX_BALANCES: &[u8] = b"balances";
 pub const PREFIX_ALLOWANCES: &[u8] = b"allowances";
+pub const PREFIX_ALLOWANCES_GIVEN: &[u8] = b"allowancesgiven";
+pub const PREFIX_ALLOWANCES_RECEIVED: &[u8] = b"allowancesreceived";
 pub const PREFIX_VIEW_KEY: &[u8] = b"viewingkey";
 pub const PREFIX_RECEIVERS: &[u8] = b"receivers";
+pub const PREFIX_MINTER_QUOTAS: &[u8] = b"minterquotas";
+pub const PREFIX_DELEGATES: &[u8] = b"delegates";
+pub const PREFIX_CHECKPOINTS: &[u8] = b"checkpoints";
+pub const PREFIX_TOTAL_SUPPLY_CHECKPOINTS: &[u8] = b"totalsupplycheckpoints";
 
 // Note that id is a globally incrementing counter.
 // Since it's 64 bits long, even at 50 tx/s it would take
 // over 11 billion years for it to rollback. I'm pretty sure
 // we'll have bigger issues by then.
-#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
 pub struct Tx {
     pub id: u64,
     pub from: HumanAddr,
     pub sender: HumanAddr,
     pub receiver: HumanAddr,
     pub coins: Coin,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    pub block_time: Option<u64>,
+    pub block_height: Option<u64>,
 }
 
-impl Tx {
-    pub fn into_stored<A: Api>(self, api: &A) -> StdResult<StoredTx> {
-        let tx = StoredTx {
-            id: self.id,
-            from: api.canonical_address(&self.from)?,
-            sender: api.canonical_address(&self.sender)?,
-            receiver: api.canonical_address(&self.receiver)?,
-            coins: self.coins,
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TxAction {
+    Transfer {
+        from: HumanAddr,
+        sender: HumanAddr,
+        recipient: HumanAddr,
+    },
+    Mint {
+        minter: HumanAddr,
+        recipient: HumanAddr,
+    },
+    Burn {
+        burner: HumanAddr,
+        owner: HumanAddr,
+    },
+    Deposit {},
+    Redeem {},
+}
+
+// SNIP-21 transaction, as returned by the `TransactionHistory` query
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct RichTx {
+    pub id: u64,
+    pub action: TxAction,
+    pub coins: Coin,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    pub block_time: u64,
+    pub block_height: u64,
+}
+
+impl RichTx {
+    /// Returns the legacy SNIP-20 view of this tx, if it's a transfer
+    pub fn into_transfer(self) -> Option<Tx> {
+        match self.action {
+            TxAction::Transfer {
+                from,
+                sender,
+                recipient,
+            } => Some(Tx {
+                id: self.id,
+                from,
+                sender,
+                receiver: recipient,
+                coins: self.coins,
+                memo: self.memo,
+                block_time: Some(self.block_time),
+                block_height: Some(self.block_height),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+enum StoredTxAction {
+    Transfer {
+        from: CanonicalAddr,
+        sender: CanonicalAddr,
+        recipient: CanonicalAddr,
+    },
+    Mint {
+        minter: CanonicalAddr,
+        recipient: CanonicalAddr,
+    },
+    Burn {
+        burner: CanonicalAddr,
+        owner: CanonicalAddr,
+    },
+    Deposit {},
+    Redeem {},
+}
+
+impl StoredTxAction {
+    fn into_humanized<A: Api>(self, api: &A) -> StdResult<TxAction> {
+        let action = match self {
+            StoredTxAction::Transfer {
+                from,
+                sender,
+                recipient,
+            } => TxAction::Transfer {
+                from: api.human_address(&from)?,
+                sender: api.human_address(&sender)?,
+                recipient: api.human_address(&recipient)?,
+            },
+            StoredTxAction::Mint { minter, recipient } => TxAction::Mint {
+                minter: api.human_address(&minter)?,
+                recipient: api.human_address(&recipient)?,
+            },
+            StoredTxAction::Burn { burner, owner } => TxAction::Burn {
+                burner: api.human_address(&burner)?,
+                owner: api.human_address(&owner)?,
+            },
+            StoredTxAction::Deposit {} => TxAction::Deposit {},
+            StoredTxAction::Redeem {} => TxAction::Redeem {},
         };
-        Ok(tx)
+        Ok(action)
     }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct StoredTx {
-    pub id: u64,
-    pub from: CanonicalAddr,
-    pub sender: CanonicalAddr,
-    pub receiver: CanonicalAddr,
-    pub coins: Coin,
+struct StoredRichTx {
+    id: u64,
+    action: StoredTxAction,
+    coins: Coin,
+    memo: Option<String>,
+    block_time: u64,
+    block_height: u64,
 }
 
-impl StoredTx {
-    pub fn into_humanized<A: Api>(self, api: &A) -> StdResult<Tx> {
-        let tx = Tx {
+impl StoredRichTx {
+    fn into_humanized<A: Api>(self, api: &A) -> StdResult<RichTx> {
+        Ok(RichTx {
             id: self.id,
-            from: api.human_address(&self.from)?,
-            sender: api.human_address(&self.sender)?,
-            receiver: api.human_address(&self.receiver)?,
+            action: self.action.into_humanized(api)?,
             coins: self.coins,
-        };
-        Ok(tx)
+            memo: self.memo,
+            block_time: self.block_time,
+            block_height: self.block_height,
+        })
     }
 }
 
-pub fn store_transfer<S: Storage>(
+fn new_tx<S: Storage>(
     store: &mut S,
-    owner: &CanonicalAddr,
-    sender: &CanonicalAddr,
-    receiver: &CanonicalAddr,
+    action: StoredTxAction,
     amount: Uint128,
     denom: String,
-) -> StdResult<()> {
+    memo: Option<String>,
+    block: &BlockInfo,
+) -> StdResult<StoredRichTx> {
     let mut config = Config::from_storage(store);
     let id = config.tx_count() + 1;
     config.set_tx_count(id)?;
 
-    let coins = Coin { denom, amount };
-    let tx = StoredTx {
+    Ok(StoredRichTx {
         id,
+        action,
+        coins: Coin { denom, amount },
+        memo,
+        block_time: block.time,
+        block_height: block.height,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn store_transfer<S: Storage>(
+    store: &mut S,
+    owner: &CanonicalAddr,
+    sender: &CanonicalAddr,
+    receiver: &CanonicalAddr,
+    amount: Uint128,
+    denom: String,
+    memo: Option<String>,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let action = StoredTxAction::Transfer {
         from: owner.clone(),
         sender: sender.clone(),
-        receiver: receiver.clone(),
-        coins,
+        recipient: receiver.clone(),
     };
+    let tx = new_tx(store, action, amount, denom, memo, block)?;
 
     if owner != sender {
-        append_tx(store, tx.clone(), &owner)?;
+        append_transfer(store, tx.clone(), &owner)?;
+    }
+    append_transfer(store, tx.clone(), &sender)?;
+    // Don't record a self transfer twice
+    if receiver != owner && receiver != sender {
+        append_transfer(store, tx, &receiver)?;
     }
-    append_tx(store, tx.clone(), &sender)?;
-    append_tx(store, tx, &receiver)?;
 
     Ok(())
 }
 
+pub fn store_mint<S: Storage>(
+    store: &mut S,
+    minter: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    amount: Uint128,
+    denom: String,
+    memo: Option<String>,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let action = StoredTxAction::Mint {
+        minter: minter.clone(),
+        recipient: recipient.clone(),
+    };
+    let tx = new_tx(store, action, amount, denom, memo, block)?;
+
+    if minter != recipient {
+        append_tx(store, tx.clone(), &minter)?;
+    }
+    append_tx(store, tx, &recipient)
+}
+
+pub fn store_burn<S: Storage>(
+    store: &mut S,
+    owner: &CanonicalAddr,
+    burner: &CanonicalAddr,
+    amount: Uint128,
+    denom: String,
+    memo: Option<String>,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let action = StoredTxAction::Burn {
+        burner: burner.clone(),
+        owner: owner.clone(),
+    };
+    let tx = new_tx(store, action, amount, denom, memo, block)?;
+
+    if burner != owner {
+        append_tx(store, tx.clone(), &burner)?;
+    }
+    append_tx(store, tx, &owner)
+}
+
+pub fn store_deposit<S: Storage>(
+    store: &mut S,
+    recipient: &CanonicalAddr,
+    amount: Uint128,
+    denom: String,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let tx = new_tx(
+        store,
+        StoredTxAction::Deposit {},
+        amount,
+        denom,
+        None,
+        block,
+    )?;
+    append_tx(store, tx, &recipient)
+}
+
+pub fn store_redeem<S: Storage>(
+    store: &mut S,
+    redeemer: &CanonicalAddr,
+    amount: Uint128,
+    denom: String,
+    block: &BlockInfo,
+) -> StdResult<()> {
+    let tx = new_tx(store, StoredTxAction::Redeem {}, amount, denom, None, block)?;
+    append_tx(store, tx, &redeemer)
+}
+
 fn append_tx<S: Storage>(
     store: &mut S,
-    tx: StoredTx,
+    tx: StoredRichTx,
     for_address: &CanonicalAddr,
 ) -> StdResult<()> {
     let mut store = PrefixedStorage::multilevel(&[PREFIX_TXS, for_address.as_slice()], store);
@@ -118,21 +314,43 @@ fn append_tx<S: Storage>(
     store.push(&tx)
 }
 
-pub fn get_transfers<A: Api, S: ReadonlyStorage>(
+/// Transfers are also indexed on their own, so the transfer history doesn't have to go through
+/// every other transaction of the account
+fn append_transfer<S: Storage>(
+    store: &mut S,
+    tx: StoredRichTx,
+    for_address: &CanonicalAddr,
+) -> StdResult<()> {
+    let position = {
+        let mut txs_store =
+            PrefixedStorage::multilevel(&[PREFIX_TXS, for_address.as_slice()], store);
+        let mut txs_store = AppendStoreMut::attach_or_create(&mut txs_store)?;
+        txs_store.push(&tx)?;
+        txs_store.len() - 1
+    };
+
+    let mut store = PrefixedStorage::multilevel(&[PREFIX_TRANSFERS, for_address.as_slice()], store);
+    let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+    store.push(&position)
+}
+
+/// Returns a page of the account's transactions, newest first, along with the total number of
+/// transactions the account has
+pub fn get_transactions<A: Api, S: ReadonlyStorage>(
     api: &A,
     storage: &S,
     for_address: &CanonicalAddr,
     page: u32,
     page_size: u32,
-) -> StdResult<Vec<Tx>> {
+) -> StdResult<(Vec<RichTx>, u64)> {
     let store = ReadonlyPrefixedStorage::multilevel(&[PREFIX_TXS, for_address.as_slice()], storage);
 
     // Try to access the storage of txs for the account.
-    // If it doesn't exist yet, return an empty list of transfers.
-    let store = if let Some(result) = AppendStore::<StoredTx, _>::attach(&store) {
+    // If it doesn't exist yet, return an empty list of transactions.
+    let store = if let Some(result) = AppendStore::<StoredRichTx, _>::attach(&store) {
         result?
     } else {
-        return Ok(vec![]);
+        return Ok((vec![], 0));
     };
 
     // Take `page_size` txs starting from the latest tx, potentially skipping `page * page_size`
@@ -140,13 +358,54 @@ pub fn get_transfers<A: Api, S: ReadonlyStorage>(
     let tx_iter = store
         .iter()
         .rev()
-        .skip((page * page_size) as _)
+        .skip(page.saturating_mul(page_size) as _)
         .take(page_size as _);
-    // The `and_then` here flattens the `StdResult<StdResult<Tx>>` to an `StdResult<Tx>`
-    let txs: StdResult<Vec<Tx>> = tx_iter
+    // The `and_then` here flattens the `StdResult<StdResult<RichTx>>` to an `StdResult<RichTx>`
+    let txs: StdResult<Vec<RichTx>> = tx_iter
         .map(|tx| tx.map(|tx| tx.into_humanized(api)).and_then(|x| x))
         .collect();
-    txs
+    txs.map(|txs| (txs, store.len() as u64))
+}
+
+/// Returns a page of the account's transfers, newest first. Transfers are read from the same
+/// store as all other transactions, through the account's transfer index
+pub fn get_transfers<A: Api, S: ReadonlyStorage>(
+    api: &A,
+    storage: &S,
+    for_address: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Vec<Tx>> {
+    let index_store =
+        ReadonlyPrefixedStorage::multilevel(&[PREFIX_TRANSFERS, for_address.as_slice()], storage);
+    let index = if let Some(result) = AppendStore::<u32, _>::attach(&index_store) {
+        result?
+    } else {
+        return Ok(vec![]);
+    };
+
+    let store = ReadonlyPrefixedStorage::multilevel(&[PREFIX_TXS, for_address.as_slice()], storage);
+    let store = AppendStore::<StoredRichTx, _>::attach(&store).ok_or_else(|| {
+        StdError::generic_err("transfers are indexed but no transactions are stored")
+    })??;
+
+    let mut txs = vec![];
+    for position in index
+        .iter()
+        .rev()
+        .skip(page.saturating_mul(page_size) as _)
+        .take(page_size as _)
+    {
+        if let Some(tx) = store
+            .get_at(position?)?
+            .into_humanized(api)?
+            .into_transfer()
+        {
+            txs.push(tx);
+        }
+    }
+
+    Ok(txs)
 }
 
 // Config
@@ -158,8 +417,13 @@ pub struct Constants {
     pub symbol: String,
     pub decimals: u8,
     pub prng_seed: Vec<u8>,
+    // Needed to validate query permits, since queries don't get an env
+    pub contract_address: HumanAddr,
     // privacy configuration
     pub total_supply_is_public: bool,
+    pub max_supply: Option<u128>,
+    // Deposits and redeems are disabled when this isn't set
+    pub native_denom: Option<String>,
 }
 
 pub struct ReadonlyConfig<'a, S: ReadonlyStorage> {
@@ -196,6 +460,10 @@ impl<'a, S: ReadonlyStorage> ReadonlyConfig<'a, S> {
     pub fn tx_count(&self) -> u64 {
         self.as_readonly().tx_count()
     }
+
+    pub fn native_reserve(&self) -> u128 {
+        self.as_readonly().native_reserve()
+    }
 }
 
 fn set_bin_data<T: Serialize, S: Storage>(storage: &mut S, key: &[u8], data: &T) -> StdResult<()> {
@@ -289,6 +557,14 @@ impl<'a, S: Storage> Config<'a, S> {
     pub fn set_tx_count(&mut self, count: u64) -> StdResult<()> {
         set_bin_data(&mut self.storage, KEY_TX_COUNT, &count)
     }
+
+    pub fn native_reserve(&self) -> u128 {
+        self.as_readonly().native_reserve()
+    }
+
+    pub fn set_native_reserve(&mut self, reserve: u128) -> StdResult<()> {
+        set_bin_data(&mut self.storage, KEY_NATIVE_RESERVE, &reserve)
+    }
 }
 
 /// This struct refactors out the readonly methods that we need for `Config` and `ReadonlyConfig`
@@ -335,6 +611,12 @@ impl<'a, S: ReadonlyStorage> ReadonlyConfigImpl<'a, S> {
     pub fn tx_count(&self) -> u64 {
         get_bin_data(self.0, KEY_TX_COUNT).unwrap_or_default()
     }
+
+    /// The amount of native coin held for deposited tokens. Minted tokens aren't backed by it and
+    /// can't be redeemed.
+    pub fn native_reserve(&self) -> u128 {
+        get_bin_data(self.0, KEY_NATIVE_RESERVE).unwrap_or_default()
+    }
 }
 
 // Balances
@@ -410,7 +692,7 @@ pub struct Allowance {
     pub expiration: Option<u64>,
 }
 
-pub fn read_allowance<S: Storage>(
+pub fn read_allowance<S: ReadonlyStorage>(
     store: &S,
     owner: &CanonicalAddr,
     spender: &CanonicalAddr,
@@ -432,7 +714,321 @@ pub fn write_allowance<S: Storage>(
         PrefixedStorage::multilevel(&[PREFIX_ALLOWANCES, owner.as_slice()], store);
     let mut owner_store = TypedStoreMut::attach(&mut owner_store);
 
-    owner_store.store(spender.as_slice(), &allowance)
+    let is_new = owner_store.may_load(spender.as_slice())?.is_none();
+    owner_store.store(spender.as_slice(), &allowance)?;
+
+    // Index the pair both ways, so allowances can be listed by owner and by spender
+    if is_new {
+        append_allowance_index(store, PREFIX_ALLOWANCES_GIVEN, owner, spender)?;
+        append_allowance_index(store, PREFIX_ALLOWANCES_RECEIVED, spender, owner)?;
+    }
+
+    Ok(())
+}
+
+fn append_allowance_index<S: Storage>(
+    store: &mut S,
+    prefix: &[u8],
+    account: &CanonicalAddr,
+    counterparty: &CanonicalAddr,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(&[prefix, account.as_slice()], store);
+    let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+    store.push(counterparty)
+}
+
+/// Returns a page of the non-zero allowances the owner gave, as (spender, allowance) pairs, along
+/// with the number of spenders the owner ever gave an allowance to. Allowances that went down to
+/// zero are left out of the page, so pages can be shorter than `page_size`. Queries don't know the
+/// block time, so expired allowances are still listed with their expiration
+pub fn get_allowances_given<A: Api, S: ReadonlyStorage>(
+    api: &A,
+    storage: &S,
+    owner: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<(HumanAddr, Allowance)>, u32)> {
+    get_allowances_page(
+        api,
+        storage,
+        PREFIX_ALLOWANCES_GIVEN,
+        owner,
+        page,
+        page_size,
+        |spender| read_allowance(storage, owner, spender),
+    )
+}
+
+/// Same as `get_allowances_given`, listing the (owner, allowance) pairs of the spender
+pub fn get_allowances_received<A: Api, S: ReadonlyStorage>(
+    api: &A,
+    storage: &S,
+    spender: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<(HumanAddr, Allowance)>, u32)> {
+    get_allowances_page(
+        api,
+        storage,
+        PREFIX_ALLOWANCES_RECEIVED,
+        spender,
+        page,
+        page_size,
+        |owner| read_allowance(storage, owner, spender),
+    )
+}
+
+/// Only the allowances in the requested page are read, since anyone can grow an account's index
+/// by giving it allowances
+fn get_allowances_page<A: Api, S: ReadonlyStorage, F>(
+    api: &A,
+    storage: &S,
+    prefix: &[u8],
+    account: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+    read: F,
+) -> StdResult<(Vec<(HumanAddr, Allowance)>, u32)>
+where
+    F: Fn(&CanonicalAddr) -> StdResult<Allowance>,
+{
+    let store = ReadonlyPrefixedStorage::multilevel(&[prefix, account.as_slice()], storage);
+    let store = match AppendStore::<CanonicalAddr, _>::attach(&store) {
+        Some(store) => store?,
+        None => return Ok((vec![], 0)),
+    };
+
+    let mut allowances = vec![];
+    for counterparty in store
+        .iter()
+        .skip(page.saturating_mul(page_size) as _)
+        .take(page_size as _)
+    {
+        let counterparty = counterparty?;
+        let allowance = read(&counterparty)?;
+        if allowance.amount > 0 {
+            allowances.push((api.human_address(&counterparty)?, allowance));
+        }
+    }
+
+    Ok((allowances, store.len()))
+}
+
+// Minter quotas
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MinterQuotaState {
+    pub amount: u128,
+    pub window: u64,
+    pub window_start: u64,
+    // Minted since `window_start`
+    pub minted: u128,
+}
+
+pub fn read_minter_quota<S: ReadonlyStorage>(
+    storage: &S,
+    minter: &CanonicalAddr,
+) -> StdResult<Option<MinterQuotaState>> {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_MINTER_QUOTAS, storage);
+    TypedStore::attach(&store).may_load(minter.as_slice())
+}
+
+pub fn write_minter_quota<S: Storage>(
+    store: &mut S,
+    minter: &CanonicalAddr,
+    quota: Option<&MinterQuotaState>,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::new(PREFIX_MINTER_QUOTAS, store);
+    match quota {
+        Some(quota) => TypedStoreMut::attach(&mut store).store(minter.as_slice(), quota),
+        None => {
+            store.remove(minter.as_slice());
+            Ok(())
+        }
+    }
+}
+
+/// Counts `amount` against the minter's quota, if it has one
+pub fn use_minter_quota<S: Storage>(
+    store: &mut S,
+    minter: &CanonicalAddr,
+    amount: u128,
+    block: u64,
+) -> StdResult<()> {
+    let mut quota = match read_minter_quota(store, minter)? {
+        Some(quota) => quota,
+        None => return Ok(()),
+    };
+
+    if block >= quota.window_start.saturating_add(quota.window) {
+        quota.window_start = block;
+        quota.minted = 0;
+    }
+
+    quota.minted = quota
+        .minted
+        .checked_add(amount)
+        .filter(|minted| *minted <= quota.amount)
+        .ok_or_else(|| {
+            StdError::generic_err(format!(
+                "This mint attempt would exceed the minter's quota of {} per {} blocks",
+                quota.amount, quota.window
+            ))
+        })?;
+
+    write_minter_quota(store, minter, Some(&quota))
+}
+
+// Voting power
+
+/// The voting power of an account (or the total supply) as of `block`, until the next checkpoint
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub block: u64,
+    pub votes: u128,
+}
+
+pub fn read_delegate<S: ReadonlyStorage>(
+    store: &S,
+    owner: &CanonicalAddr,
+) -> StdResult<Option<CanonicalAddr>> {
+    let delegates_store = ReadonlyPrefixedStorage::new(PREFIX_DELEGATES, store);
+    TypedStore::attach(&delegates_store).may_load(owner.as_slice())
+}
+
+pub fn write_delegate<S: Storage>(
+    store: &mut S,
+    owner: &CanonicalAddr,
+    delegatee: &CanonicalAddr,
+) -> StdResult<()> {
+    let mut delegates_store = PrefixedStorage::new(PREFIX_DELEGATES, store);
+    TypedStoreMut::attach(&mut delegates_store).store(owner.as_slice(), delegatee)
+}
+
+/// Moves `amount` votes between the delegatees of two accounts. `None` stands for tokens that
+/// aren't delegated, or that are being minted or burned
+pub fn move_voting_power<S: Storage>(
+    store: &mut S,
+    from: Option<&CanonicalAddr>,
+    to: Option<&CanonicalAddr>,
+    amount: u128,
+    block: u64,
+) -> StdResult<()> {
+    if from == to || amount == 0 {
+        return Ok(());
+    }
+
+    if let Some(from) = from {
+        write_checkpoint(
+            store,
+            &[PREFIX_CHECKPOINTS, from.as_slice()],
+            block,
+            |votes| {
+                votes.checked_sub(amount).ok_or_else(|| {
+                    StdError::generic_err("Voting power underflow. This should never happen")
+                })
+            },
+        )?;
+    }
+    if let Some(to) = to {
+        write_checkpoint(
+            store,
+            &[PREFIX_CHECKPOINTS, to.as_slice()],
+            block,
+            |votes| {
+                votes
+                    .checked_add(amount)
+                    .ok_or_else(|| StdError::generic_err("Voting power overflow"))
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn write_total_supply_checkpoint<S: Storage>(
+    store: &mut S,
+    total_supply: u128,
+    block: u64,
+) -> StdResult<()> {
+    write_checkpoint(store, &[PREFIX_TOTAL_SUPPLY_CHECKPOINTS], block, |_| {
+        Ok(total_supply)
+    })
+}
+
+/// Returns the votes delegated to the account at the end of `block`, or currently if it's `None`
+pub fn get_voting_power<S: ReadonlyStorage>(
+    storage: &S,
+    account: &CanonicalAddr,
+    block: Option<u64>,
+) -> StdResult<u128> {
+    read_checkpoint(storage, &[PREFIX_CHECKPOINTS, account.as_slice()], block)
+}
+
+/// Returns the total supply at the end of `block`, or currently if it's `None`
+pub fn get_total_voting_power<S: ReadonlyStorage>(
+    storage: &S,
+    block: Option<u64>,
+) -> StdResult<u128> {
+    read_checkpoint(storage, &[PREFIX_TOTAL_SUPPLY_CHECKPOINTS], block)
+}
+
+fn write_checkpoint<S: Storage, F: FnOnce(u128) -> StdResult<u128>>(
+    store: &mut S,
+    namespaces: &[&[u8]],
+    block: u64,
+    update: F,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(namespaces, store);
+    let mut store = AppendStoreMut::<Checkpoint, _>::attach_or_create(&mut store)?;
+
+    let len = store.len();
+    let last = if len > 0 {
+        Some(store.get_at(len - 1)?)
+    } else {
+        None
+    };
+    let checkpoint = Checkpoint {
+        block,
+        votes: update(last.map_or(0, |last| last.votes))?,
+    };
+
+    // Only keep the last change of each block
+    match last {
+        Some(last) if last.block == block => store.set_at(len - 1, &checkpoint),
+        _ => store.push(&checkpoint),
+    }
+}
+
+fn read_checkpoint<S: ReadonlyStorage>(
+    storage: &S,
+    namespaces: &[&[u8]],
+    block: Option<u64>,
+) -> StdResult<u128> {
+    let store = ReadonlyPrefixedStorage::multilevel(namespaces, storage);
+    let store = if let Some(result) = AppendStore::<Checkpoint, _>::attach(&store) {
+        result?
+    } else {
+        return Ok(0);
+    };
+    let block = block.unwrap_or(u64::MAX);
+
+    // Binary search for the first checkpoint after `block`. The one before it is the one we want
+    let (mut low, mut high) = (0, store.len());
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if store.get_at(mid)?.block <= block {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    if low == 0 {
+        Ok(0)
+    } else {
+        Ok(store.get_at(low - 1)?.votes)
+    }
 }
 
 // Viewing Keys
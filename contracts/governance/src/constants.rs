@@ -0,0 +1,5 @@
+pub const CONFIG_KEY: &[u8] = b"config";
+pub const PREFIX_PROPOSALS: &[u8] = b"proposals";
+pub const PREFIX_VOTES: &[u8] = b"votes";
+
+pub const BPS_DENOMINATOR: u128 = 10_000;
@@ -0,0 +1,793 @@
+use cosmwasm_std::{
+    from_binary, log, to_binary, Api, Binary, CosmosMsg, Env, Extern, HandleResponse, HumanAddr,
+    InitResponse, Querier, StdError, StdResult, Storage, Uint128, WasmMsg,
+};
+use secret_toolkit::snip20;
+use secret_toolkit::storage::{TypedStore, TypedStoreMut};
+
+use crate::constants::{BPS_DENOMINATOR, CONFIG_KEY};
+use crate::querier::{query_total_voting_power, query_voting_power};
+use crate::state::{
+    get_proposal, get_proposals, read_vote, store_new_proposal, update_proposal, write_vote,
+};
+use scrt_finance::governance_msg::GovernanceResponseStatus::Success;
+use scrt_finance::governance_msg::{
+    GovernanceHandleAnswer, GovernanceHandleMsg, GovernanceInitMsg, GovernanceQueryAnswer,
+    GovernanceQueryMsg, GovernanceReceiveMsg,
+};
+use scrt_finance::governance_types::{
+    GovernanceConfig, Proposal, ProposalMsg, ProposalStatus, VoteOption,
+};
+use scrt_finance::math::{add, mul};
+use scrt_finance::permit::{pubkey_to_account, Permit};
+
+pub fn init<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: GovernanceInitMsg,
+) -> StdResult<InitResponse> {
+    let config = GovernanceConfig {
+        gov_token: msg.gov_token,
+        min_deposit: msg.min_deposit,
+        voting_period: msg.voting_period,
+        quorum_bps: msg.quorum_bps,
+        threshold_bps: msg.threshold_bps,
+    };
+    validate_config(&config)?;
+
+    // Quorum is counted against the gov token's total voting power, which it must make public
+    query_total_voting_power(deps, config.gov_token.clone(), env.block.height).map_err(|err| {
+        StdError::generic_err(format!(
+            "can't query the gov token's total voting power: {}",
+            err
+        ))
+    })?;
+
+    TypedStoreMut::attach(&mut deps.storage).store(CONFIG_KEY, &config)?;
+
+    // Deposits are sent to this contract
+    let messages = vec![snip20::register_receive_msg(
+        env.contract_code_hash,
+        None,
+        1, // This is public data, no need to pad
+        config.gov_token.contract_hash,
+        config.gov_token.address,
+    )?];
+
+    Ok(InitResponse {
+        messages,
+        log: vec![],
+    })
+}
+
+pub fn handle<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: GovernanceHandleMsg,
+) -> StdResult<HandleResponse> {
+    match msg {
+        GovernanceHandleMsg::Vote {
+            proposal_id,
+            vote,
+            permit,
+        } => cast_vote(deps, env, proposal_id, vote, permit),
+        GovernanceHandleMsg::Finalize { proposal_id } => finalize(deps, env, proposal_id),
+        GovernanceHandleMsg::Execute { proposal_id } => execute(deps, proposal_id),
+        GovernanceHandleMsg::Receive {
+            from, amount, msg, ..
+        } => receive(deps, env, from, amount, msg),
+        GovernanceHandleMsg::UpdateConfig {
+            min_deposit,
+            voting_period,
+            quorum_bps,
+            threshold_bps,
+        } => update_config(
+            deps,
+            env,
+            min_deposit,
+            voting_period,
+            quorum_bps,
+            threshold_bps,
+        ),
+    }
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: GovernanceQueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        GovernanceQueryMsg::Config {} => to_binary(&GovernanceQueryAnswer::Config {
+            config: TypedStore::attach(&deps.storage).load(CONFIG_KEY)?,
+        }),
+        GovernanceQueryMsg::Proposal { proposal_id } => {
+            to_binary(&GovernanceQueryAnswer::Proposal {
+                proposal: get_proposal(&deps.storage, proposal_id)?,
+            })
+        }
+        GovernanceQueryMsg::Proposals { page, page_size } => {
+            let (proposals, total) = get_proposals(&deps.storage, page.unwrap_or(0), page_size)?;
+            to_binary(&GovernanceQueryAnswer::Proposals { proposals, total })
+        }
+    }
+}
+
+// Handle functions
+
+fn receive<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from: HumanAddr,
+    amount: Uint128,
+    msg: Binary,
+) -> StdResult<HandleResponse> {
+    let config: GovernanceConfig = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    if env.message.sender != config.gov_token.address {
+        return Err(StdError::generic_err(
+            "deposits are only accepted in the governance token",
+        ));
+    }
+
+    let msg: GovernanceReceiveMsg = from_binary(&msg)?;
+
+    match msg {
+        GovernanceReceiveMsg::CreateProposal {
+            title,
+            description,
+            msgs,
+        } => create_proposal(deps, env, config, from, amount, title, description, msgs),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_proposal<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    config: GovernanceConfig,
+    proposer: HumanAddr,
+    deposit: Uint128,
+    title: String,
+    description: String,
+    msgs: Vec<ProposalMsg>,
+) -> StdResult<HandleResponse> {
+    if deposit.u128() < config.min_deposit.u128() {
+        return Err(StdError::generic_err(format!(
+            "the minimum deposit for a proposal is {}",
+            config.min_deposit
+        )));
+    }
+
+    // Votes are counted as they were before the proposal was made, so they can't be bought in
+    // reaction to it
+    let snapshot_block = env.block.height.saturating_sub(1);
+    let total_voting_power = query_total_voting_power(deps, config.gov_token, snapshot_block)?;
+
+    let mut proposal = Proposal {
+        id: 0,
+        proposer,
+        title,
+        description,
+        msgs,
+        deposit,
+        snapshot_block,
+        total_voting_power: Uint128(total_voting_power),
+        end_block: env.block.height + config.voting_period,
+        yes: Uint128(0),
+        no: Uint128(0),
+        abstain: Uint128(0),
+        status: ProposalStatus::Voting,
+    };
+    let proposal_id = store_new_proposal(&mut deps.storage, &mut proposal)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("proposal_id", proposal_id)],
+        data: Some(to_binary(&GovernanceHandleAnswer::CreateProposal {
+            proposal_id,
+        })?),
+    })
+}
+
+fn cast_vote<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    proposal_id: u64,
+    vote: VoteOption,
+    permit: Permit,
+) -> StdResult<HandleResponse> {
+    let config: GovernanceConfig = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let mut proposal = get_proposal(&deps.storage, proposal_id)?;
+    if proposal.status != ProposalStatus::Voting || env.block.height > proposal.end_block {
+        return Err(StdError::generic_err(format!(
+            "voting on proposal {} has ended",
+            proposal_id
+        )));
+    }
+
+    // The gov token verifies the signature, we only make sure the permit is the voter's own
+    let voter = pubkey_to_account(&permit.signature.pub_key.value)?;
+    if voter != env.message.sender {
+        return Err(StdError::generic_err(
+            "the permit must be signed by the voter",
+        ));
+    }
+    if read_vote(&deps.storage, proposal_id, &voter)?.is_some() {
+        return Err(StdError::generic_err(format!(
+            "{} already voted on proposal {}",
+            voter, proposal_id
+        )));
+    }
+
+    let votes = query_voting_power(deps, config.gov_token, permit, proposal.snapshot_block)?;
+    if votes == 0 {
+        return Err(StdError::generic_err(format!(
+            "{} had no voting power at block {}",
+            voter, proposal.snapshot_block
+        )));
+    }
+
+    let count = match vote {
+        VoteOption::Yes => &mut proposal.yes,
+        VoteOption::No => &mut proposal.no,
+        VoteOption::Abstain => &mut proposal.abstain,
+    };
+    *count = Uint128(add(count.u128(), votes)?);
+
+    update_proposal(&mut deps.storage, &proposal)?;
+    write_vote(&mut deps.storage, proposal_id, &voter, vote)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&GovernanceHandleAnswer::Vote {
+            status: Success,
+        })?),
+    })
+}
+
+fn finalize<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    proposal_id: u64,
+) -> StdResult<HandleResponse> {
+    let config: GovernanceConfig = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let mut proposal = get_proposal(&deps.storage, proposal_id)?;
+    if proposal.status != ProposalStatus::Voting {
+        return Err(StdError::generic_err(format!(
+            "proposal {} was already finalized",
+            proposal_id
+        )));
+    }
+    if env.block.height <= proposal.end_block {
+        return Err(StdError::generic_err(format!(
+            "voting on proposal {} ends at block {}",
+            proposal_id, proposal.end_block
+        )));
+    }
+
+    proposal.status = tally(&proposal, &config)?;
+
+    // Deposits of proposals that didn't reach quorum are burned, to discourage spam
+    let messages = if proposal.status == ProposalStatus::Expired {
+        vec![snip20::burn_msg(
+            proposal.deposit,
+            None,
+            1,
+            config.gov_token.contract_hash,
+            config.gov_token.address,
+        )?]
+    } else {
+        vec![snip20::transfer_msg(
+            proposal.proposer.clone(),
+            proposal.deposit,
+            None,
+            1,
+            config.gov_token.contract_hash,
+            config.gov_token.address,
+        )?]
+    };
+
+    update_proposal(&mut deps.storage, &proposal)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![log("status", format!("{:?}", proposal.status))],
+        data: Some(to_binary(&GovernanceHandleAnswer::Finalize {
+            status: Success,
+        })?),
+    })
+}
+
+fn execute<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    proposal_id: u64,
+) -> StdResult<HandleResponse> {
+    let mut proposal = get_proposal(&deps.storage, proposal_id)?;
+    if proposal.status != ProposalStatus::Passed {
+        return Err(StdError::generic_err(format!(
+            "proposal {} can't be executed, its status is {:?}",
+            proposal_id, proposal.status
+        )));
+    }
+
+    // If any of the msgs fails this whole tx is reverted, and the proposal stays `Passed`
+    let messages: Vec<CosmosMsg> = proposal
+        .msgs
+        .iter()
+        .map(|proposal_msg| {
+            WasmMsg::Execute {
+                contract_addr: proposal_msg.contract.address.clone(),
+                callback_code_hash: proposal_msg.contract.contract_hash.clone(),
+                msg: proposal_msg.msg.clone(),
+                send: vec![],
+            }
+            .into()
+        })
+        .collect();
+
+    proposal.status = ProposalStatus::Executed;
+    update_proposal(&mut deps.storage, &proposal)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&GovernanceHandleAnswer::Execute {
+            status: Success,
+        })?),
+    })
+}
+
+fn update_config<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    min_deposit: Option<Uint128>,
+    voting_period: Option<u64>,
+    quorum_bps: Option<u16>,
+    threshold_bps: Option<u16>,
+) -> StdResult<HandleResponse> {
+    // Changes to the rules have to go through a proposal themselves
+    if env.message.sender != env.contract.address {
+        return Err(StdError::generic_err(
+            "the config can only be changed by a proposal",
+        ));
+    }
+
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: GovernanceConfig = config_store.load(CONFIG_KEY)?;
+
+    if let Some(min_deposit) = min_deposit {
+        config.min_deposit = min_deposit;
+    }
+    if let Some(voting_period) = voting_period {
+        config.voting_period = voting_period;
+    }
+    if let Some(quorum_bps) = quorum_bps {
+        config.quorum_bps = quorum_bps;
+    }
+    if let Some(threshold_bps) = threshold_bps {
+        config.threshold_bps = threshold_bps;
+    }
+    validate_config(&config)?;
+
+    config_store.store(CONFIG_KEY, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&GovernanceHandleAnswer::UpdateConfig {
+            status: Success,
+        })?),
+    })
+}
+
+// Helper functions
+
+fn tally(proposal: &Proposal, config: &GovernanceConfig) -> StdResult<ProposalStatus> {
+    let (yes, no, abstain) = (
+        proposal.yes.u128(),
+        proposal.no.u128(),
+        proposal.abstain.u128(),
+    );
+
+    let votes = add(add(yes, no)?, abstain)?;
+    let quorum = mul(
+        proposal.total_voting_power.u128(),
+        config.quorum_bps as u128,
+    )?;
+    if mul(votes, BPS_DENOMINATOR)? < quorum {
+        return Ok(ProposalStatus::Expired);
+    }
+
+    if mul(yes, BPS_DENOMINATOR)? > mul(add(yes, no)?, config.threshold_bps as u128)? {
+        Ok(ProposalStatus::Passed)
+    } else {
+        Ok(ProposalStatus::Rejected)
+    }
+}
+
+fn validate_config(config: &GovernanceConfig) -> StdResult<()> {
+    if config.voting_period == 0 {
+        return Err(StdError::generic_err("the voting period can't be empty"));
+    }
+    if config.quorum_bps as u128 > BPS_DENOMINATOR {
+        return Err(StdError::generic_err(format!(
+            "quorum can't be higher than {} bps",
+            BPS_DENOMINATOR
+        )));
+    }
+    if config.threshold_bps as u128 > BPS_DENOMINATOR {
+        return Err(StdError::generic_err(format!(
+            "threshold can't be higher than {} bps",
+            BPS_DENOMINATOR
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::querier::GovTokenQueryMsg;
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, MockApi, MockStorage, MOCK_CONTRACT_ADDR,
+    };
+    use cosmwasm_std::{from_slice, Empty, QuerierResult, QueryRequest, WasmQuery};
+    use scrt_finance::lp_staking_types::SecretContract;
+    use scrt_finance::permit::{PermitParams, PermitSignature, PubKey};
+
+    const VOTER: &str = "secret1wv66lgmt6azsngtdcqrkgyt7hjsk3r9lkvzqcx";
+
+    // Answers every voting power query with the same amount, as the gov token would for a valid permit,
+    // and total voting power queries with `total_voting_power`
+    struct VotingPowerQuerier {
+        voting_power: u128,
+        total_voting_power: u128,
+    }
+
+    impl Querier for VotingPowerQuerier {
+        fn raw_query(&self, request: &[u8]) -> QuerierResult {
+            let msg = match from_slice::<QueryRequest<Empty>>(request).unwrap() {
+                QueryRequest::Wasm(WasmQuery::Smart { msg, .. }) => msg,
+                _ => panic!("Unexpected query"),
+            };
+            let response = match from_binary(&msg).unwrap() {
+                GovTokenQueryMsg::TotalVotingPowerAt { .. } => format!(
+                    r#"{{"total_voting_power_at":{{"total_voting_power":"{}"}}}}"#,
+                    self.total_voting_power
+                ),
+                GovTokenQueryMsg::WithPermit { .. } => format!(
+                    r#"{{"voting_power":{{"voting_power":"{}"}}}}"#,
+                    self.voting_power
+                ),
+            };
+            Ok(Ok(Binary::from(response.as_bytes())))
+        }
+    }
+
+    fn init_helper(voting_power: u128) -> Extern<MockStorage, MockApi, VotingPowerQuerier> {
+        let deps = mock_dependencies(20, &[]);
+        let mut deps = Extern {
+            storage: deps.storage,
+            api: deps.api,
+            querier: VotingPowerQuerier {
+                voting_power,
+                total_voting_power: 10_000,
+            },
+        };
+        init(&mut deps, mock_env("admin", &[]), init_msg()).unwrap();
+
+        deps
+    }
+
+    fn init_msg() -> GovernanceInitMsg {
+        GovernanceInitMsg {
+            gov_token: SecretContract {
+                address: HumanAddr("sefi".to_string()),
+                contract_hash: "1".to_string(),
+            },
+            min_deposit: Uint128(1000),
+            voting_period: 100,
+            quorum_bps: 500,
+            threshold_bps: 5_000,
+        }
+    }
+
+    fn env_at(sender: &str, height: u64) -> Env {
+        let mut env = mock_env(sender, &[]);
+        env.block.height = height;
+        env
+    }
+
+    fn voter_permit() -> Permit {
+        Permit {
+            params: PermitParams {
+                allowed_tokens: vec![HumanAddr("sefi".to_string())],
+                permit_name: "test".to_string(),
+                chain_id: "secret-4".to_string(),
+                permissions: vec![scrt_finance::permit::Permission::Balance],
+            },
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary::from_base64("Aq5U7xmLb4QZjttvvflKkSN8wD9ywJ8brS/IhFKlww5F")
+                        .unwrap(),
+                },
+                // The mock querier doesn't check signatures
+                signature: Binary::from(vec![0u8; 64]),
+            },
+        }
+    }
+
+    fn create_proposal_msg(deposit: u128, msgs: Vec<ProposalMsg>) -> GovernanceHandleMsg {
+        GovernanceHandleMsg::Receive {
+            sender: HumanAddr("proposer".to_string()),
+            from: HumanAddr("proposer".to_string()),
+            amount: Uint128(deposit),
+            msg: to_binary(&GovernanceReceiveMsg::CreateProposal {
+                title: "title".to_string(),
+                description: "description".to_string(),
+                msgs,
+            })
+            .unwrap(),
+        }
+    }
+
+    fn extract_error_msg<T>(result: StdResult<T>) -> String {
+        match result {
+            Ok(_) => panic!("expected an error"),
+            Err(StdError::GenericErr { msg, .. }) => msg,
+            Err(err) => panic!("unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_init_requires_total_voting_power() {
+        // The mocked gov token can't be queried, like one that keeps its total supply private
+        let mut deps = mock_dependencies(20, &[]);
+        let result = init(&mut deps, mock_env("admin", &[]), init_msg());
+        assert!(extract_error_msg(result).contains("total voting power"));
+    }
+
+    #[test]
+    fn test_proposal_lifecycle() {
+        let mut deps = init_helper(600);
+
+        let set_minters = ProposalMsg {
+            contract: SecretContract {
+                address: HumanAddr("sefi".to_string()),
+                contract_hash: "1".to_string(),
+            },
+            msg: Binary::from(br#"{"set_minters":{"minters":[]}}"#.to_vec()),
+        };
+
+        // Deposits must be paid in the gov token, and be large enough
+        let result = handle(
+            &mut deps,
+            env_at("other-token", 1000),
+            create_proposal_msg(1000, vec![set_minters.clone()]),
+        );
+        assert!(extract_error_msg(result).contains("only accepted in the governance token"));
+        let result = handle(
+            &mut deps,
+            env_at("sefi", 1000),
+            create_proposal_msg(999, vec![set_minters.clone()]),
+        );
+        assert!(extract_error_msg(result).contains("minimum deposit"));
+
+        handle(
+            &mut deps,
+            env_at("sefi", 1000),
+            create_proposal_msg(1000, vec![set_minters.clone()]),
+        )
+        .unwrap();
+
+        // Only the permit's signer can vote with it, and only once
+        let vote_msg = GovernanceHandleMsg::Vote {
+            proposal_id: 0,
+            vote: VoteOption::Yes,
+            permit: voter_permit(),
+        };
+        let result = handle(&mut deps, env_at("someone", 1050), vote_msg.clone());
+        assert!(extract_error_msg(result).contains("must be signed by the voter"));
+        handle(&mut deps, env_at(VOTER, 1050), vote_msg.clone()).unwrap();
+        let result = handle(&mut deps, env_at(VOTER, 1051), vote_msg);
+        assert!(extract_error_msg(result).contains("already voted"));
+
+        let finalize_msg = GovernanceHandleMsg::Finalize { proposal_id: 0 };
+        let result = handle(&mut deps, env_at("anyone", 1100), finalize_msg.clone());
+        assert!(extract_error_msg(result).contains("ends at block 1100"));
+
+        // Finalizing only refunds the deposit, the msgs are sent by `Execute`
+        let execute_msg = GovernanceHandleMsg::Execute { proposal_id: 0 };
+        let result = handle(&mut deps, env_at("anyone", 1101), execute_msg.clone());
+        assert!(extract_error_msg(result).contains("its status is Voting"));
+
+        let response = handle(&mut deps, env_at("anyone", 1101), finalize_msg.clone()).unwrap();
+        assert_eq!(
+            response.messages,
+            vec![snip20::transfer_msg(
+                HumanAddr("proposer".to_string()),
+                Uint128(1000),
+                None,
+                1,
+                "1".to_string(),
+                HumanAddr("sefi".to_string()),
+            )
+            .unwrap()]
+        );
+
+        let query_msg = GovernanceQueryMsg::Proposal { proposal_id: 0 };
+        match from_binary(&query(&deps, query_msg.clone()).unwrap()).unwrap() {
+            GovernanceQueryAnswer::Proposal { proposal } => {
+                assert_eq!(proposal.status, ProposalStatus::Passed);
+                assert_eq!(proposal.yes, Uint128(600));
+                assert_eq!(proposal.snapshot_block, 999);
+                assert_eq!(proposal.total_voting_power, Uint128(10_000));
+            }
+            _ => panic!("Unexpected"),
+        }
+
+        let result = handle(&mut deps, env_at("anyone", 1102), finalize_msg);
+        assert!(extract_error_msg(result).contains("already finalized"));
+
+        let response = handle(&mut deps, env_at("anyone", 1102), execute_msg.clone()).unwrap();
+        assert_eq!(
+            response.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr("sefi".to_string()),
+                callback_code_hash: "1".to_string(),
+                msg: set_minters.msg,
+                send: vec![],
+            })]
+        );
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            GovernanceQueryAnswer::Proposal { proposal } => {
+                assert_eq!(proposal.status, ProposalStatus::Executed);
+            }
+            _ => panic!("Unexpected"),
+        }
+
+        // A proposal is only executed once
+        let result = handle(&mut deps, env_at("anyone", 1103), execute_msg);
+        assert!(extract_error_msg(result).contains("its status is Executed"));
+    }
+
+    #[test]
+    fn test_proposal_without_quorum() {
+        let mut deps = init_helper(400);
+
+        handle(
+            &mut deps,
+            env_at("sefi", 1000),
+            create_proposal_msg(1000, vec![]),
+        )
+        .unwrap();
+        let vote_msg = GovernanceHandleMsg::Vote {
+            proposal_id: 0,
+            vote: VoteOption::Yes,
+            permit: voter_permit(),
+        };
+        handle(&mut deps, env_at(VOTER, 1001), vote_msg).unwrap();
+
+        // Nothing is executed, and the deposit is burned instead of returned
+        let finalize_msg = GovernanceHandleMsg::Finalize { proposal_id: 0 };
+        let response = handle(&mut deps, env_at("anyone", 1101), finalize_msg).unwrap();
+        assert_eq!(
+            response.messages,
+            vec![snip20::burn_msg(
+                Uint128(1000),
+                None,
+                1,
+                "1".to_string(),
+                HumanAddr("sefi".to_string()),
+            )
+            .unwrap()]
+        );
+
+        let query_msg = GovernanceQueryMsg::Proposals {
+            page: None,
+            page_size: 10,
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            GovernanceQueryAnswer::Proposals { proposals, total } => {
+                assert_eq!(total, 1);
+                assert_eq!(proposals[0].status, ProposalStatus::Expired);
+            }
+            _ => panic!("Unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_quorum_follows_total_voting_power() {
+        let mut deps = init_helper(600);
+
+        // 600 votes are 6% of the total voting power, enough for a 5% quorum
+        handle(
+            &mut deps,
+            env_at("sefi", 1000),
+            create_proposal_msg(1000, vec![]),
+        )
+        .unwrap();
+
+        // Once the supply grows, the same votes are only 3%
+        deps.querier.total_voting_power = 20_000;
+        handle(
+            &mut deps,
+            env_at("sefi", 1000),
+            create_proposal_msg(1000, vec![]),
+        )
+        .unwrap();
+
+        for proposal_id in 0..2 {
+            let vote_msg = GovernanceHandleMsg::Vote {
+                proposal_id,
+                vote: VoteOption::No,
+                permit: voter_permit(),
+            };
+            handle(&mut deps, env_at(VOTER, 1001), vote_msg).unwrap();
+            let finalize_msg = GovernanceHandleMsg::Finalize { proposal_id };
+            handle(&mut deps, env_at("anyone", 1101), finalize_msg).unwrap();
+        }
+
+        let query_msg = GovernanceQueryMsg::Proposals {
+            page: None,
+            page_size: 10,
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            GovernanceQueryAnswer::Proposals { proposals, .. } => {
+                assert_eq!(proposals[0].total_voting_power, Uint128(20_000));
+                assert_eq!(proposals[0].status, ProposalStatus::Expired);
+                assert_eq!(proposals[1].total_voting_power, Uint128(10_000));
+                assert_eq!(proposals[1].status, ProposalStatus::Rejected);
+            }
+            _ => panic!("Unexpected"),
+        }
+
+        // Only passed proposals can be executed
+        let execute_msg = GovernanceHandleMsg::Execute { proposal_id: 0 };
+        let result = handle(&mut deps, env_at("anyone", 1102), execute_msg);
+        assert!(extract_error_msg(result).contains("its status is Rejected"));
+    }
+
+    #[test]
+    fn test_update_config() {
+        let mut deps = init_helper(0);
+
+        let update_msg = GovernanceHandleMsg::UpdateConfig {
+            min_deposit: None,
+            voting_period: None,
+            quorum_bps: Some(1),
+            threshold_bps: Some(6_667),
+        };
+        let result = handle(&mut deps, mock_env("admin", &[]), update_msg.clone());
+        assert!(extract_error_msg(result).contains("can only be changed by a proposal"));
+        handle(&mut deps, mock_env(MOCK_CONTRACT_ADDR, &[]), update_msg).unwrap();
+
+        let update_msg = GovernanceHandleMsg::UpdateConfig {
+            min_deposit: None,
+            voting_period: None,
+            quorum_bps: None,
+            threshold_bps: Some(10_001),
+        };
+        let result = handle(&mut deps, mock_env(MOCK_CONTRACT_ADDR, &[]), update_msg);
+        assert!(extract_error_msg(result).contains("threshold can't be higher"));
+
+        let update_msg = GovernanceHandleMsg::UpdateConfig {
+            min_deposit: None,
+            voting_period: None,
+            quorum_bps: Some(10_001),
+            threshold_bps: None,
+        };
+        let result = handle(&mut deps, mock_env(MOCK_CONTRACT_ADDR, &[]), update_msg);
+        assert!(extract_error_msg(result).contains("quorum can't be higher"));
+
+        match from_binary(&query(&deps, GovernanceQueryMsg::Config {}).unwrap()).unwrap() {
+            GovernanceQueryAnswer::Config { config } => {
+                assert_eq!(config.quorum_bps, 1);
+                assert_eq!(config.threshold_bps, 6_667);
+            }
+            _ => panic!("Unexpected"),
+        }
+    }
+}
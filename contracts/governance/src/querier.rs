@@ -0,0 +1,76 @@
+use cosmwasm_std::{
+    to_binary, Api, Extern, Querier, QueryRequest, StdError, StdResult, Storage, Uint128, WasmQuery,
+};
+use schemars::JsonSchema;
+use scrt_finance::lp_staking_types::SecretContract;
+use scrt_finance::permit::Permit;
+use serde::{Deserialize, Serialize};
+
+// The subset of the gov token's queries used by this contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GovTokenQueryMsg {
+    TotalVotingPowerAt {
+        block: u64,
+    },
+    WithPermit {
+        permit: Permit,
+        query: GovTokenQueryWithPermit,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GovTokenQueryWithPermit {
+    VotingPower { block: Option<u64> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GovTokenQueryAnswer {
+    VotingPower { voting_power: Uint128 },
+    TotalVotingPowerAt { total_voting_power: Uint128 },
+}
+
+pub fn query_voting_power<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    gov_token: SecretContract,
+    permit: Permit,
+    block: u64,
+) -> StdResult<u128> {
+    let response = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        callback_code_hash: gov_token.contract_hash,
+        contract_addr: gov_token.address,
+        msg: to_binary(&GovTokenQueryMsg::WithPermit {
+            permit,
+            query: GovTokenQueryWithPermit::VotingPower { block: Some(block) },
+        })?,
+    }))?;
+
+    match response {
+        GovTokenQueryAnswer::VotingPower { voting_power } => Ok(voting_power.u128()),
+        _ => Err(StdError::generic_err("unexpected voting power response")),
+    }
+}
+
+// Fails if the gov token keeps its total supply private
+pub fn query_total_voting_power<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    gov_token: SecretContract,
+    block: u64,
+) -> StdResult<u128> {
+    let response = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        callback_code_hash: gov_token.contract_hash,
+        contract_addr: gov_token.address,
+        msg: to_binary(&GovTokenQueryMsg::TotalVotingPowerAt { block })?,
+    }))?;
+
+    match response {
+        GovTokenQueryAnswer::TotalVotingPowerAt { total_voting_power } => {
+            Ok(total_voting_power.u128())
+        }
+        _ => Err(StdError::generic_err(
+            "unexpected total voting power response",
+        )),
+    }
+}
@@ -0,0 +1,88 @@
+use cosmwasm_std::{HumanAddr, ReadonlyStorage, StdError, StdResult, Storage};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use scrt_finance::governance_types::{Proposal, VoteOption};
+use secret_toolkit::storage::{AppendStore, AppendStoreMut, TypedStore, TypedStoreMut};
+
+use crate::constants::{PREFIX_PROPOSALS, PREFIX_VOTES};
+
+// Proposal ids are their index in the store
+pub fn store_new_proposal<S: Storage>(store: &mut S, proposal: &mut Proposal) -> StdResult<u64> {
+    let mut store = PrefixedStorage::new(PREFIX_PROPOSALS, store);
+    let mut store = AppendStoreMut::<Proposal, _>::attach_or_create(&mut store)?;
+
+    proposal.id = store.len() as u64;
+    store.push(proposal)?;
+
+    Ok(proposal.id)
+}
+
+pub fn update_proposal<S: Storage>(store: &mut S, proposal: &Proposal) -> StdResult<()> {
+    let mut store = PrefixedStorage::new(PREFIX_PROPOSALS, store);
+    let mut store = AppendStoreMut::<Proposal, _>::attach_or_create(&mut store)?;
+
+    store.set_at(proposal.id as u32, proposal)
+}
+
+pub fn get_proposal<S: ReadonlyStorage>(storage: &S, proposal_id: u64) -> StdResult<Proposal> {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_PROPOSALS, storage);
+    let store = AppendStore::<Proposal, _>::attach(&store).unwrap_or_else(|| {
+        Err(StdError::generic_err(format!(
+            "proposal {} doesn't exist",
+            proposal_id
+        )))
+    })?;
+
+    if proposal_id >= store.len() as u64 {
+        return Err(StdError::generic_err(format!(
+            "proposal {} doesn't exist",
+            proposal_id
+        )));
+    }
+
+    store.get_at(proposal_id as u32)
+}
+
+// Newest first
+pub fn get_proposals<S: ReadonlyStorage>(
+    storage: &S,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<Proposal>, u64)> {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_PROPOSALS, storage);
+    let store = if let Some(result) = AppendStore::<Proposal, _>::attach(&store) {
+        result?
+    } else {
+        return Ok((vec![], 0));
+    };
+
+    let proposals: StdResult<Vec<Proposal>> = store
+        .iter()
+        .rev()
+        .skip((page * page_size) as usize)
+        .take(page_size as usize)
+        .collect();
+
+    proposals.map(|proposals| (proposals, store.len() as u64))
+}
+
+pub fn read_vote<S: ReadonlyStorage>(
+    storage: &S,
+    proposal_id: u64,
+    voter: &HumanAddr,
+) -> StdResult<Option<VoteOption>> {
+    let store =
+        ReadonlyPrefixedStorage::multilevel(&[PREFIX_VOTES, &proposal_id.to_be_bytes()], storage);
+
+    TypedStore::attach(&store).may_load(voter.0.as_bytes())
+}
+
+pub fn write_vote<S: Storage>(
+    store: &mut S,
+    proposal_id: u64,
+    voter: &HumanAddr,
+    vote: VoteOption,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(&[PREFIX_VOTES, &proposal_id.to_be_bytes()], store);
+
+    TypedStoreMut::attach(&mut store).store(voter.0.as_bytes(), &vote)
+}
@@ -2,9 +2,18 @@ pub const CONFIG_KEY: &[u8] = b"config";
 pub const REWARD_POOL_KEY: &[u8] = b"rewardpool";
 pub const TOKEN_INFO_KEY: &[u8] = b"tokeninfo";
 pub const VIEWING_KEY_KEY: &[u8] = b"viewingkey";
+pub const TX_COUNT_KEY: &[u8] = b"txcount";
+pub const PREFIX_TXS: &[u8] = b"txs";
+pub const LAST_DEPOSIT_KEY: &[u8] = b"lastdeposit";
+pub const VESTING_KEY: &[u8] = b"vesting";
 
 pub const RESPONSE_BLOCK_SIZE: usize = 256;
 
+// Blocks stakers have after the deadline to claim their rewards, before the admin can reclaim the pool
+pub const CLAIM_GRACE_PERIOD: u64 = 100_800; // ~1 week
+
 // TODO: get those as an input for specific coins, as some coins might require different scales than others
 // pub const INC_TOKEN_SCALE: u128 = 1; // LP Tokens and Gov Token is at the same scale
 pub const REWARD_SCALE: u128 = 1_000_000_000_000; // 10 ^ 12
+
+pub const BPS_DENOMINATOR: u128 = 10_000;
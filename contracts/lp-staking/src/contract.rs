@@ -1,5 +1,5 @@
 use cosmwasm_std::{
-    debug_print, from_binary, to_binary, Api, Binary, CosmosMsg, Env, Extern, HandleResponse,
+    debug_print, from_binary, log, to_binary, Api, Binary, CosmosMsg, Env, Extern, HandleResponse,
     HumanAddr, InitResponse, Querier, ReadonlyStorage, StdError, StdResult, Storage, Uint128,
     WasmMsg,
 };
@@ -7,18 +7,23 @@ use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
 use secret_toolkit::crypto::sha_256;
 use secret_toolkit::snip20;
 use secret_toolkit::storage::{TypedStore, TypedStoreMut};
-use secret_toolkit::utils::{pad_handle_result, pad_query_result};
 
 use crate::constants::*;
 use crate::querier::query_pending;
-use crate::state::Config;
+use crate::state::{get_txs, store_tx, Config, Migration, VestingSchedule};
 use scrt_finance::lp_staking_msg::LPStakingResponseStatus::Success;
 use scrt_finance::lp_staking_msg::{
-    LPStakingHandleAnswer, LPStakingHandleMsg, LPStakingHookMsg, LPStakingInitMsg,
-    LPStakingQueryAnswer, LPStakingQueryMsg, LPStakingReceiveAnswer, LPStakingReceiveMsg,
+    ContractStatusLevel, LPStakingHandleAnswer, LPStakingHandleMsg, LPStakingHookMsg,
+    LPStakingInitMsg, LPStakingQueryAnswer, LPStakingQueryMsg, LPStakingQueryWithPermit,
+    LPStakingReceiveAnswer, LPStakingReceiveMsg,
+};
+use scrt_finance::lp_staking_types::{
+    Fees, ResiduePolicy, RewardPool, SecretContract, TokenInfo, TxAction, UserInfo, Vesting,
 };
-use scrt_finance::lp_staking_types::{RewardPool, TokenInfo, UserInfo};
 use scrt_finance::master_msg::MasterHandleMsg;
+use scrt_finance::math::{add, mul_div, sub};
+use scrt_finance::padding::{pad_handle_result, pad_query_result};
+use scrt_finance::permit::{validate, Permission, Permit};
 use scrt_finance::viewing_key::{ViewingKey, VIEWING_KEY_SIZE};
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
@@ -38,8 +43,23 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
             master: msg.master,
             viewing_key: msg.viewing_key.clone(),
             prng_seed: prng_seed_hashed.to_vec(),
-            is_stopped: false,
+            status: ContractStatusLevel::NormalRun,
             own_addr: env.contract.address,
+            deadline: u64::MAX,
+            approved_depositors: vec![],
+            total_deposit_cap: None,
+            user_deposit_cap: None,
+            deposit_allowlist: None,
+            fees: Fees {
+                deposit_fee_bps: 0,
+                withdraw_fee_bps: 0,
+                withdraw_fee_period: 0,
+                treasury: None,
+            },
+            vesting: None,
+            migration: None,
+            predecessor: None,
+            residue_policy: ResiduePolicy::FirstStaker,
         },
     )?;
 
@@ -49,6 +69,13 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
             residue: 0,
             inc_token_supply: 0,
             acc_reward_per_share: 0,
+            last_reward_block: env.block.height,
+            claimed_reward_per_share: 0,
+            total_rewards_distributed: 0,
+            rewards_owed: 0,
+            vesting_outstanding: 0,
+            residue_stream_start: 0,
+            residue_stream_end: 0,
         },
     )?;
 
@@ -99,10 +126,13 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
     msg: LPStakingHandleMsg,
 ) -> StdResult<HandleResponse> {
     let config: Config = TypedStoreMut::attach(&mut deps.storage).load(CONFIG_KEY)?;
-    if config.is_stopped {
+    if config.status == ContractStatusLevel::StopAll {
         return match msg {
             LPStakingHandleMsg::EmergencyRedeem {} => emergency_redeem(deps, env),
             LPStakingHandleMsg::ResumeContract {} => resume_contract(deps, env),
+            LPStakingHandleMsg::SetContractStatus { level } => {
+                set_contract_status(deps, env, level)
+            }
             _ => Err(StdError::generic_err(
                 "this contract is stopped and this action is not allowed",
             )),
@@ -111,6 +141,8 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
 
     let response = match msg {
         LPStakingHandleMsg::Redeem { amount } => redeem(deps, env, amount),
+        LPStakingHandleMsg::Claim { recipient, msg } => claim(deps, env, recipient, msg),
+        LPStakingHandleMsg::EmergencyRedeem {} => emergency_redeem(deps, env),
         LPStakingHandleMsg::Receive {
             from, amount, msg, ..
         } => receive(deps, env, from, amount.u128(), msg),
@@ -118,8 +150,38 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
             create_viewing_key(deps, env, entropy)
         }
         LPStakingHandleMsg::SetViewingKey { key, .. } => set_viewing_key(deps, env, key),
+        LPStakingHandleMsg::RevokePermit { permit_name, .. } => {
+            revoke_permit(deps, env, permit_name)
+        }
         LPStakingHandleMsg::StopContract {} => stop_contract(deps, env),
+        LPStakingHandleMsg::ResumeContract {} => resume_contract(deps, env),
+        LPStakingHandleMsg::SetContractStatus { level } => set_contract_status(deps, env, level),
         LPStakingHandleMsg::ChangeAdmin { address } => change_admin(deps, env, address),
+        LPStakingHandleMsg::SetDeadline { block } => set_deadline(deps, env, block),
+        LPStakingHandleMsg::SetApprovedDepositors { depositors } => {
+            set_approved_depositors(deps, env, depositors)
+        }
+        LPStakingHandleMsg::SetDepositCaps {
+            total_cap,
+            user_cap,
+        } => set_deposit_caps(deps, env, total_cap, user_cap),
+        LPStakingHandleMsg::SetDepositAllowlist { allowlist } => {
+            set_deposit_allowlist(deps, env, allowlist)
+        }
+        LPStakingHandleMsg::SetFees { fees } => set_fees(deps, env, fees),
+        LPStakingHandleMsg::SetVesting { vesting } => set_vesting(deps, env, vesting),
+        LPStakingHandleMsg::ExitVesting {} => exit_vesting(deps, env),
+        LPStakingHandleMsg::Migrate {} => migrate(deps, env),
+        LPStakingHandleMsg::MigrateTo { new_contract } => migrate_to(deps, env, new_contract),
+        LPStakingHandleMsg::MigrateUsers { users } => migrate_users(deps, env, users),
+        LPStakingHandleMsg::SetPredecessor { address } => set_predecessor(deps, env, address),
+        LPStakingHandleMsg::RecoverTokens {
+            token,
+            amount,
+            recipient,
+        } => recover_tokens(deps, env, token, amount.u128(), recipient),
+        LPStakingHandleMsg::SetResiduePolicy { policy } => set_residue_policy(deps, env, policy),
+        LPStakingHandleMsg::ClaimRewardPool { to } => claim_reward_pool(deps, env, to),
         LPStakingHandleMsg::NotifyAllocation { amount, hook } => notify_allocation(
             deps,
             env,
@@ -140,7 +202,16 @@ pub fn query<S: Storage, A: Api, Q: Querier>(
         LPStakingQueryMsg::ContractStatus {} => query_contract_status(deps),
         LPStakingQueryMsg::RewardToken {} => query_reward_token(deps),
         LPStakingQueryMsg::IncentivizedToken {} => query_incentivized_token(deps),
+        LPStakingQueryMsg::Deadline {} => query_deadline(deps),
+        LPStakingQueryMsg::ApprovedDepositors {} => query_approved_depositors(deps),
+        LPStakingQueryMsg::PoolInfo {} => query_pool_info(deps),
+        LPStakingQueryMsg::DepositCapacity {} => query_deposit_capacity(deps),
+        LPStakingQueryMsg::Fees {} => query_fees(deps),
+        LPStakingQueryMsg::VestingConfig {} => query_vesting_config(deps),
+        LPStakingQueryMsg::MigrationStatus {} => query_migration_status(deps),
+        LPStakingQueryMsg::ResiduePolicy {} => query_residue_policy(deps),
         LPStakingQueryMsg::TokenInfo {} => query_token_info(deps),
+        LPStakingQueryMsg::WithPermit { permit, query } => permit_queries(deps, permit, query),
         _ => authenticated_queries(deps, msg),
     };
 
@@ -163,9 +234,21 @@ pub fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
     } else if key.check_viewing_key(expected_key.unwrap().as_slice()) {
         return match msg {
             LPStakingQueryMsg::Rewards {
-                address, height, ..
-            } => query_pending_rewards(deps, &address, height),
+                address,
+                height,
+                include_pending,
+                ..
+            } => query_pending_rewards(deps, &address, height, include_pending.unwrap_or(true)),
             LPStakingQueryMsg::Balance { address, .. } => query_deposit(deps, &address),
+            LPStakingQueryMsg::History {
+                address,
+                page,
+                page_size,
+                ..
+            } => query_history(deps, &address, page.unwrap_or(0), page_size),
+            LPStakingQueryMsg::Vesting {
+                address, height, ..
+            } => query_vesting(deps, &address, height),
             _ => panic!("This should never happen"),
         };
     }
@@ -175,6 +258,38 @@ pub fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
     })?)
 }
 
+fn permit_queries<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    permit: Permit,
+    query: LPStakingQueryWithPermit,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let address = validate(&deps.storage, &permit, &config.own_addr)?;
+
+    let required = match query {
+        LPStakingQueryWithPermit::History { .. } => Permission::History,
+        _ => Permission::Balance,
+    };
+    if !permit.check_permission(&required) {
+        return Err(StdError::generic_err(format!(
+            "permit doesn't grant the {:?} permission",
+            required
+        )));
+    }
+
+    match query {
+        LPStakingQueryWithPermit::Rewards {
+            height,
+            include_pending,
+        } => query_pending_rewards(deps, &address, height, include_pending.unwrap_or(true)),
+        LPStakingQueryWithPermit::Balance {} => query_deposit(deps, &address),
+        LPStakingQueryWithPermit::History { page, page_size } => {
+            query_history(deps, &address, page.unwrap_or(0), page_size)
+        }
+        LPStakingQueryWithPermit::Vesting { height } => query_vesting(deps, &address, height),
+    }
+}
+
 // Handle functions
 
 fn receive<S: Storage, A: Api, Q: Querier>(
@@ -187,7 +302,20 @@ fn receive<S: Storage, A: Api, Q: Querier>(
     let msg: LPStakingReceiveMsg = from_binary(&msg)?;
 
     match msg {
-        LPStakingReceiveMsg::Deposit {} => deposit(deps, env, from, amount),
+        LPStakingReceiveMsg::Deposit {} => deposit(
+            deps,
+            env,
+            LPStakingHookMsg::Deposit {
+                from,
+                amount: Uint128(amount),
+            },
+        ),
+        LPStakingReceiveMsg::DepositFor { beneficiary } => {
+            deposit_for(deps, env, from, beneficiary, amount)
+        }
+        LPStakingReceiveMsg::Migrate { beneficiary } => {
+            receive_migrated(deps, env, from, beneficiary, amount)
+        }
     }
 }
 
@@ -204,7 +332,7 @@ fn notify_allocation<S: Storage, A: Api, Q: Querier>(
         ));
     }
 
-    let reward_pool = update_rewards(deps, /*&env, &config,*/ amount)?;
+    let (reward_pool, mut messages) = update_rewards(deps, &env, &config, amount)?;
 
     let mut response = Ok(HandleResponse {
         messages: vec![],
@@ -215,24 +343,105 @@ fn notify_allocation<S: Storage, A: Api, Q: Querier>(
     if let Some(hook_msg) = hook {
         response = match hook_msg {
             LPStakingHookMsg::Deposit { from, amount } => {
-                deposit_hook(deps, env, config, reward_pool, from, amount.u128())
+                deposit_hook(deps, env, config, reward_pool, from, amount.u128(), true)
             }
+            LPStakingHookMsg::DepositFor {
+                beneficiary,
+                amount,
+            } => deposit_hook(
+                deps,
+                env,
+                config,
+                reward_pool,
+                beneficiary,
+                amount.u128(),
+                false,
+            ),
+            LPStakingHookMsg::ReceiveMigrated {
+                beneficiary,
+                amount,
+            } => receive_migrated_hook(deps, env, config, reward_pool, beneficiary, amount.u128()),
             LPStakingHookMsg::Redeem { to, amount } => {
                 redeem_hook(deps, env, config, reward_pool, to, amount)
             }
+            LPStakingHookMsg::Claim {
+                from,
+                recipient,
+                msg,
+            } => claim_hook(deps, env, config, reward_pool, from, recipient, msg),
+            LPStakingHookMsg::Migrate { users } => {
+                migrate_hook(deps, env, config, reward_pool, users)
+            }
         }
     }
 
-    response
+    // Messages from the allocation itself go first
+    response.map(|mut response| {
+        messages.append(&mut response.messages);
+        response.messages = messages;
+        response
+    })
 }
 
 fn deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    hook: LPStakingHookMsg,
+) -> StdResult<HandleResponse> {
+    // Ensure that the sent tokens are from an expected contract address
+    let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+    if env.message.sender != config.inc_token.address {
+        return Err(StdError::generic_err(format!(
+            "This token is not supported. Supported: {}, given: {}",
+            config.inc_token.address, env.message.sender
+        )));
+    }
+    if config.status != ContractStatusLevel::NormalRun {
+        return Err(StdError::generic_err("deposits are currently paused"));
+    }
+    if let Some(migration) = &config.migration {
+        return Err(StdError::generic_err(format!(
+            "this pool is being migrated to {}, deposit there instead",
+            migration.new_contract.address
+        )));
+    }
+
+    update_allocation(env, config, Some(to_binary(&hook)?))
+}
+
+fn deposit_for<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    depositor: HumanAddr,
+    beneficiary: HumanAddr,
+    amount: u128,
+) -> StdResult<HandleResponse> {
+    let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+    if !config.approved_depositors.contains(&depositor) {
+        return Err(StdError::generic_err(format!(
+            "not an approved depositor: {}",
+            depositor
+        )));
+    }
+
+    // The stake is owned by the beneficiary from this point on
+    deposit(
+        deps,
+        env,
+        LPStakingHookMsg::DepositFor {
+            beneficiary,
+            amount: Uint128(amount),
+        },
+    )
+}
+
+fn receive_migrated<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     from: HumanAddr,
+    beneficiary: HumanAddr,
     amount: u128,
 ) -> StdResult<HandleResponse> {
-    // Ensure that the sent tokens are from an expected contract address
     let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
     if env.message.sender != config.inc_token.address {
         return Err(StdError::generic_err(format!(
@@ -240,12 +449,30 @@ fn deposit<S: Storage, A: Api, Q: Querier>(
             config.inc_token.address, env.message.sender
         )));
     }
+    if config.predecessor != Some(from.clone()) {
+        return Err(StdError::generic_err(format!(
+            "stakes can only be migrated from the predecessor contract, got: {}",
+            from
+        )));
+    }
+    // Pausing deposits doesn't stop stakes that were already in the pool from coming over
+    if config.status == ContractStatusLevel::StopAll {
+        return Err(StdError::generic_err(
+            "this contract is stopped and this action is not allowed",
+        ));
+    }
+    if let Some(migration) = &config.migration {
+        return Err(StdError::generic_err(format!(
+            "this pool is being migrated to {}, migrate there instead",
+            migration.new_contract.address
+        )));
+    }
 
     update_allocation(
         env,
         config,
-        Some(to_binary(&LPStakingHookMsg::Deposit {
-            from,
+        Some(to_binary(&LPStakingHookMsg::ReceiveMigrated {
+            beneficiary,
             amount: Uint128(amount),
         })?),
     )
@@ -253,38 +480,95 @@ fn deposit<S: Storage, A: Api, Q: Querier>(
 
 fn deposit_hook<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    _env: Env,
+    env: Env,
     config: Config,
     mut reward_pool: RewardPool,
     from: HumanAddr,
     amount: u128,
+    own_deposit: bool,
 ) -> StdResult<HandleResponse> {
+    if let Some(allowlist) = &config.deposit_allowlist {
+        if !allowlist.contains(&from) {
+            return Err(StdError::generic_err(format!(
+                "address is not allowed to deposit to this pool: {}",
+                from
+            )));
+        }
+    }
+
     let mut messages: Vec<CosmosMsg> = vec![];
-    let mut users_store = TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage);
-    let mut user = users_store
-        .load(from.0.as_bytes())
-        .unwrap_or(UserInfo { locked: 0, debt: 0 }); // NotFound is the only possible error
+    let fee = get_fee(amount, config.fees.deposit_fee_bps)?;
+    if fee > 0 {
+        messages.push(fee_msg(&config.fees, &config.inc_token, fee)?);
+    }
+    let amount = amount - fee;
 
-    if user.locked > 0 {
-        let pending = user.locked * reward_pool.acc_reward_per_share / REWARD_SCALE - user.debt;
-        if pending > 0 {
-            messages.push(secret_toolkit::snip20::transfer_msg(
-                from.clone(),
-                Uint128(pending),
-                None,
-                RESPONSE_BLOCK_SIZE,
-                config.reward_token.contract_hash,
-                config.reward_token.address,
-            )?);
+    // Otherwise anyone could keep restarting someone else's withdrawal fee period
+    if own_deposit {
+        let mut last_deposit_store = PrefixedStorage::new(LAST_DEPOSIT_KEY, &mut deps.storage);
+        TypedStoreMut::attach(&mut last_deposit_store)
+            .store(from.0.as_bytes(), &env.block.height)?;
+    }
+
+    let locked = TypedStore::<UserInfo, S>::attach(&deps.storage)
+        .load(from.0.as_bytes())
+        .map_or(0, |user| user.locked); // NotFound is the only possible error
+    if let Some(cap) = config.user_deposit_cap {
+        if add(locked, amount)? > cap {
+            return Err(StdError::generic_err(format!(
+                "deposit exceeds the per-user cap: cap={}, locked={}, deposit={}",
+                cap, locked, amount,
+            )));
+        }
+    }
+    if let Some(cap) = config.total_deposit_cap {
+        if add(reward_pool.inc_token_supply, amount)? > cap {
+            return Err(StdError::generic_err(format!(
+                "deposit exceeds the pool cap: cap={}, total locked={}, deposit={}",
+                cap, reward_pool.inc_token_supply, amount,
+            )));
         }
     }
 
-    user.locked += amount;
-    user.debt = user.locked * reward_pool.acc_reward_per_share / REWARD_SCALE;
-    users_store.store(from.0.as_bytes(), &user)?;
+    credit_stake(
+        deps,
+        &env,
+        &config,
+        &mut reward_pool,
+        &from,
+        amount,
+        &mut messages,
+    )?;
 
-    reward_pool.inc_token_supply += amount;
-    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+    Ok(HandleResponse {
+        messages,
+        log: vec![log("deposit_fee", fee)],
+        data: Some(to_binary(&LPStakingReceiveAnswer::Deposit {
+            status: Success,
+        })?),
+    })
+}
+
+/// Migrated stakes already went through the predecessor's fees and limits, so they are credited
+/// as they are
+fn receive_migrated_hook<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    config: Config,
+    mut reward_pool: RewardPool,
+    beneficiary: HumanAddr,
+    amount: u128,
+) -> StdResult<HandleResponse> {
+    let mut messages: Vec<CosmosMsg> = vec![];
+    credit_stake(
+        deps,
+        &env,
+        &config,
+        &mut reward_pool,
+        &beneficiary,
+        amount,
+        &mut messages,
+    )?;
 
     Ok(HandleResponse {
         messages,
@@ -295,6 +579,71 @@ fn deposit_hook<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+/// Adds `amount` to the user's stake, harvesting their pending rewards first
+fn credit_stake<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    config: &Config,
+    reward_pool: &mut RewardPool,
+    from: &HumanAddr,
+    amount: u128,
+    messages: &mut Vec<CosmosMsg>,
+) -> StdResult<()> {
+    let mut users_store = TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage);
+    let mut user = users_store
+        .load(from.0.as_bytes())
+        .unwrap_or(UserInfo { locked: 0, debt: 0 }); // NotFound is the only possible error
+
+    let mut pending = 0;
+    if user.locked > 0 {
+        pending = get_pending(&user, reward_pool)?;
+    }
+
+    user.locked = add(user.locked, amount)?;
+    user.debt = get_debt(user.locked, reward_pool.acc_reward_per_share)?;
+    users_store.store(from.0.as_bytes(), &user)?;
+
+    let payout = harvest(
+        &mut deps.storage,
+        config,
+        reward_pool,
+        from,
+        pending,
+        env.block.height,
+    )?;
+    if payout > 0 {
+        messages.push(secret_toolkit::snip20::transfer_msg(
+            from.clone(),
+            Uint128(payout),
+            None,
+            RESPONSE_BLOCK_SIZE,
+            config.reward_token.contract_hash.clone(),
+            config.reward_token.address.clone(),
+        )?);
+        store_tx(
+            &mut deps.storage,
+            from,
+            TxAction::ClaimRewards,
+            payout,
+            env.block.height,
+            reward_pool.acc_reward_per_share,
+        )?;
+    }
+    store_tx(
+        &mut deps.storage,
+        from,
+        TxAction::Deposit,
+        amount,
+        env.block.height,
+        reward_pool.acc_reward_per_share,
+    )?;
+
+    reward_pool.inc_token_supply = add(reward_pool.inc_token_supply, amount)?;
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, reward_pool)?;
+
+    Ok(())
+}
+
 fn redeem<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -332,7 +681,7 @@ fn redeem_hook<S: Storage, A: Api, Q: Querier>(
     }
 
     let mut messages: Vec<CosmosMsg> = vec![];
-    let pending = user.locked * reward_pool.acc_reward_per_share / REWARD_SCALE - user.debt;
+    let pending = get_pending(&user, &reward_pool)?;
     debug_print(format!("DEBUG DEBUG DEBUG"));
     debug_print(format!(
         "reward pool: | residue: {} | total supply: {} | acc: {} |",
@@ -344,29 +693,58 @@ fn redeem_hook<S: Storage, A: Api, Q: Querier>(
     ));
     debug_print(format!("pending: {}", pending));
     debug_print(format!("DEBUG DEBUG DEBUG"));
-    if pending > 0 {
+    let payout = harvest(
+        &mut deps.storage,
+        &config,
+        &mut reward_pool,
+        &to,
+        pending,
+        env.block.height,
+    )?;
+    if payout > 0 {
         // Transfer rewards
         messages.push(secret_toolkit::snip20::transfer_msg(
             to.clone(),
-            Uint128(pending),
+            Uint128(payout),
             None,
             RESPONSE_BLOCK_SIZE,
             config.reward_token.contract_hash,
             config.reward_token.address,
         )?);
+        store_tx(
+            &mut deps.storage,
+            &to,
+            TxAction::ClaimRewards,
+            payout,
+            env.block.height,
+            reward_pool.acc_reward_per_share,
+        )?;
     }
 
     // Transfer redeemed tokens
     user.locked -= amount;
-    user.debt = user.locked * reward_pool.acc_reward_per_share / REWARD_SCALE;
+    user.debt = get_debt(user.locked, reward_pool.acc_reward_per_share)?;
     TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage).store(to.0.as_bytes(), &user)?;
+    store_tx(
+        &mut deps.storage,
+        &to,
+        TxAction::Redeem,
+        amount,
+        env.block.height,
+        reward_pool.acc_reward_per_share,
+    )?;
 
-    reward_pool.inc_token_supply -= amount;
+    reward_pool.inc_token_supply = sub(reward_pool.inc_token_supply, amount)?;
     TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
 
+    let fee = get_withdraw_fee(&deps.storage, &config, &to, amount, env.block.height)?;
+    if fee > 0 {
+        messages.push(fee_msg(&config.fees, &config.inc_token, fee)?);
+    }
+
     messages.push(secret_toolkit::snip20::transfer_msg(
         to,
-        Uint128(amount),
+        Uint128(amount - fee),
         None,
         RESPONSE_BLOCK_SIZE,
         config.inc_token.contract_hash,
@@ -375,13 +753,231 @@ fn redeem_hook<S: Storage, A: Api, Q: Querier>(
 
     Ok(HandleResponse {
         messages,
-        log: vec![],
+        log: vec![log("withdraw_fee", fee)],
         data: Some(to_binary(&LPStakingHandleAnswer::Redeem {
             status: Success,
         })?),
     })
 }
 
+fn claim<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    recipient: Option<HumanAddr>,
+    msg: Option<Binary>,
+) -> StdResult<HandleResponse> {
+    let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+    let from = env.message.sender.clone();
+
+    update_allocation(
+        env,
+        config,
+        Some(to_binary(&LPStakingHookMsg::Claim {
+            recipient: recipient.unwrap_or_else(|| from.clone()),
+            from,
+            msg,
+        })?),
+    )
+}
+
+fn claim_hook<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    config: Config,
+    mut reward_pool: RewardPool,
+    from: HumanAddr,
+    recipient: HumanAddr,
+    msg: Option<Binary>,
+) -> StdResult<HandleResponse> {
+    let mut users_store = TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage);
+    let mut user = users_store
+        .load(from.0.as_bytes())
+        .unwrap_or(UserInfo { locked: 0, debt: 0 }); // NotFound is the only possible error
+
+    let pending = get_pending(&user, &reward_pool)?;
+    user.debt = get_debt(user.locked, reward_pool.acc_reward_per_share)?;
+    users_store.store(from.0.as_bytes(), &user)?;
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    let payout = harvest(
+        &mut deps.storage,
+        &config,
+        &mut reward_pool,
+        &from,
+        pending,
+        env.block.height,
+    )?;
+    if payout > 0 {
+        store_tx(
+            &mut deps.storage,
+            &from,
+            TxAction::ClaimRewards,
+            payout,
+            env.block.height,
+            reward_pool.acc_reward_per_share,
+        )?;
+
+        // Sending allows the rewards to be forwarded straight into another contract
+        messages.push(if msg.is_some() {
+            snip20::send_msg(
+                recipient,
+                Uint128(payout),
+                msg,
+                None,
+                RESPONSE_BLOCK_SIZE,
+                config.reward_token.contract_hash,
+                config.reward_token.address,
+            )?
+        } else {
+            snip20::transfer_msg(
+                recipient,
+                Uint128(payout),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                config.reward_token.contract_hash,
+                config.reward_token.address,
+            )?
+        });
+    }
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&LPStakingHandleAnswer::Claim {
+            status: Success,
+        })?),
+    })
+}
+
+fn migrate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+    if config.migration.is_none() {
+        return Err(StdError::generic_err("this contract is not being migrated"));
+    }
+    let users = vec![env.message.sender.clone()];
+
+    update_allocation(
+        env,
+        config,
+        Some(to_binary(&LPStakingHookMsg::Migrate { users })?),
+    )
+}
+
+fn migrate_users<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    users: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let config = TypedStore::<Config, S>::attach(&deps.storage).load(CONFIG_KEY)?;
+    enforce_admin(config.clone(), env.clone())?;
+    if config.migration.is_none() {
+        return Err(StdError::generic_err("this contract is not being migrated"));
+    }
+
+    update_allocation(
+        env,
+        config,
+        Some(to_binary(&LPStakingHookMsg::Migrate { users })?),
+    )
+}
+
+/// Settles the users' rewards, and moves their whole stake to the successor contract
+fn migrate_hook<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    mut config: Config,
+    mut reward_pool: RewardPool,
+    users: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let mut migration = config
+        .migration
+        .clone()
+        .ok_or_else(|| StdError::generic_err("this contract is not being migrated"))?;
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    let mut migrated_users = 0;
+    for address in users {
+        let user = TypedStore::<UserInfo, S>::attach(&deps.storage)
+            .load(address.0.as_bytes())
+            .unwrap_or(UserInfo { locked: 0, debt: 0 }); // NotFound is the only possible error
+        if user.locked == 0 {
+            continue;
+        }
+
+        let pending = get_pending(&user, &reward_pool)?;
+        TypedStoreMut::<UserInfo, S>::attach(&mut deps.storage)
+            .store(address.0.as_bytes(), &UserInfo { locked: 0, debt: 0 })?;
+
+        let payout = harvest(
+            &mut deps.storage,
+            &config,
+            &mut reward_pool,
+            &address,
+            pending,
+            env.block.height,
+        )?;
+        if payout > 0 {
+            messages.push(snip20::transfer_msg(
+                address.clone(),
+                Uint128(payout),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                config.reward_token.contract_hash.clone(),
+                config.reward_token.address.clone(),
+            )?);
+            store_tx(
+                &mut deps.storage,
+                &address,
+                TxAction::ClaimRewards,
+                payout,
+                env.block.height,
+                reward_pool.acc_reward_per_share,
+            )?;
+        }
+
+        messages.push(snip20::send_msg(
+            migration.new_contract.address.clone(),
+            Uint128(user.locked),
+            Some(to_binary(&LPStakingReceiveMsg::Migrate {
+                beneficiary: address.clone(),
+            })?),
+            None,
+            RESPONSE_BLOCK_SIZE,
+            config.inc_token.contract_hash.clone(),
+            config.inc_token.address.clone(),
+        )?);
+        store_tx(
+            &mut deps.storage,
+            &address,
+            TxAction::Migrate,
+            user.locked,
+            env.block.height,
+            reward_pool.acc_reward_per_share,
+        )?;
+
+        reward_pool.inc_token_supply = sub(reward_pool.inc_token_supply, user.locked)?;
+        migration.migrated_users += 1;
+        migration.migrated_amount = add(migration.migrated_amount, user.locked)?;
+        migrated_users += 1;
+    }
+
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+    config.migration = Some(migration);
+    TypedStoreMut::attach(&mut deps.storage).store(CONFIG_KEY, &config)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![log("migrated_users", migrated_users)],
+        data: Some(to_binary(&LPStakingHandleAnswer::Migrate {
+            status: Success,
+        })?),
+    })
+}
+
 pub fn create_viewing_key<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -421,28 +1017,23 @@ pub fn set_viewing_key<S: Storage, A: Api, Q: Querier>(
     })
 }
 
-fn stop_contract<S: Storage, A: Api, Q: Querier>(
+fn revoke_permit<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
+    permit_name: String,
 ) -> StdResult<HandleResponse> {
-    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
-    let mut config: Config = config_store.load(CONFIG_KEY)?;
-
-    enforce_admin(config.clone(), env)?;
-
-    config.is_stopped = true;
-    config_store.store(CONFIG_KEY, &config)?;
+    scrt_finance::permit::revoke_permit(&mut deps.storage, &env.message.sender, &permit_name);
 
     Ok(HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&LPStakingHandleAnswer::StopContract {
+        data: Some(to_binary(&LPStakingHandleAnswer::RevokePermit {
             status: Success,
         })?),
     })
 }
 
-fn resume_contract<S: Storage, A: Api, Q: Querier>(
+fn stop_contract<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
 ) -> StdResult<HandleResponse> {
@@ -451,7 +1042,28 @@ fn resume_contract<S: Storage, A: Api, Q: Querier>(
 
     enforce_admin(config.clone(), env)?;
 
-    config.is_stopped = false;
+    config.status = ContractStatusLevel::StopAll;
+    config_store.store(CONFIG_KEY, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&LPStakingHandleAnswer::StopContract {
+            status: Success,
+        })?),
+    })
+}
+
+fn resume_contract<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: Config = config_store.load(CONFIG_KEY)?;
+
+    enforce_admin(config.clone(), env)?;
+
+    config.status = ContractStatusLevel::NormalRun;
     config_store.store(CONFIG_KEY, &config)?;
 
     Ok(HandleResponse {
@@ -463,6 +1075,28 @@ fn resume_contract<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+fn set_contract_status<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    level: ContractStatusLevel,
+) -> StdResult<HandleResponse> {
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: Config = config_store.load(CONFIG_KEY)?;
+
+    enforce_admin(config.clone(), env)?;
+
+    config.status = level;
+    config_store.store(CONFIG_KEY, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&LPStakingHandleAnswer::SetContractStatus {
+            status: Success,
+        })?),
+    })
+}
+
 fn change_admin<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -485,713 +1119,2755 @@ fn change_admin<S: Storage, A: Api, Q: Querier>(
     })
 }
 
-/// YOU SHOULD NEVER USE THIS! This will erase any eligibility for rewards you earned so far
-fn emergency_redeem<S: Storage, A: Api, Q: Querier>(
+fn set_deadline<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
+    block: u64,
 ) -> StdResult<HandleResponse> {
-    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
-    let mut user: UserInfo = TypedStoreMut::attach(&mut deps.storage)
-        .load(env.message.sender.0.as_bytes())
-        .unwrap_or(UserInfo { locked: 0, debt: 0 });
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: Config = config_store.load(CONFIG_KEY)?;
 
-    let mut reward_pool: RewardPool =
-        TypedStoreMut::attach(&mut deps.storage).load(REWARD_POOL_KEY)?;
-    reward_pool.inc_token_supply -= user.locked;
-    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+    enforce_admin(config.clone(), env.clone())?;
 
-    let mut messages = vec![];
-    if user.locked > 0 {
-        messages.push(secret_toolkit::snip20::transfer_msg(
-            env.message.sender.clone(),
-            Uint128(user.locked),
-            None,
-            RESPONSE_BLOCK_SIZE,
-            config.inc_token.contract_hash,
-            config.inc_token.address,
-        )?);
+    if block < env.block.height {
+        return Err(StdError::generic_err(format!(
+            "deadline can't be in the past: current block={}, deadline={}",
+            env.block.height, block
+        )));
     }
 
-    user = UserInfo { locked: 0, debt: 0 };
-    TypedStoreMut::attach(&mut deps.storage).store(env.message.sender.0.as_bytes(), &user)?;
+    config.deadline = block;
+    config_store.store(CONFIG_KEY, &config)?;
 
     Ok(HandleResponse {
-        messages,
-        log: vec![],
-        data: Some(to_binary(&LPStakingHandleAnswer::EmergencyRedeem {
+        messages: vec![],
+        log: vec![log("set_deadline", block)],
+        data: Some(to_binary(&LPStakingHandleAnswer::SetDeadline {
             status: Success,
         })?),
     })
 }
 
-// Query functions
+fn set_approved_depositors<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    depositors: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: Config = config_store.load(CONFIG_KEY)?;
 
-fn query_pending_rewards<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    address: &HumanAddr,
-    block: u64,
-) -> StdResult<Binary> {
-    let new_rewards = query_pending(deps, block)?;
-    let reward_pool = TypedStore::<RewardPool, S>::attach(&deps.storage).load(REWARD_POOL_KEY)?;
-    let user = TypedStore::<UserInfo, S>::attach(&deps.storage)
-        .load(address.0.as_bytes())
-        .unwrap_or(UserInfo { locked: 0, debt: 0 });
-    let mut acc_reward_per_share = reward_pool.acc_reward_per_share;
+    enforce_admin(config.clone(), env)?;
 
-    if reward_pool.inc_token_supply != 0 {
-        acc_reward_per_share +=
-            (new_rewards + reward_pool.residue) * REWARD_SCALE / reward_pool.inc_token_supply;
-    }
+    config.approved_depositors = depositors;
+    config_store.store(CONFIG_KEY, &config)?;
 
-    to_binary(&LPStakingQueryAnswer::Rewards {
-        // This is not necessarily accurate, since we don't validate new_rewards. It is up to
-        // the UI to display accurate numbers
-        rewards: Uint128(user.locked * acc_reward_per_share / REWARD_SCALE - user.debt),
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&LPStakingHandleAnswer::SetApprovedDepositors {
+            status: Success,
+        })?),
     })
 }
 
-fn query_deposit<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    address: &HumanAddr,
-) -> StdResult<Binary> {
-    let user = TypedStore::attach(&deps.storage)
-        .load(address.0.as_bytes())
-        .unwrap_or(UserInfo { locked: 0, debt: 0 });
+fn set_deposit_caps<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    total_cap: Option<Uint128>,
+    user_cap: Option<Uint128>,
+) -> StdResult<HandleResponse> {
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: Config = config_store.load(CONFIG_KEY)?;
 
-    to_binary(&LPStakingQueryAnswer::Balance {
-        amount: Uint128(user.locked),
-    })
-}
+    enforce_admin(config.clone(), env)?;
 
-fn query_contract_status<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-) -> StdResult<Binary> {
-    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    // Lowering a cap below what's already locked only blocks new deposits, nobody is forced out
+    config.total_deposit_cap = total_cap.map(|c| c.u128());
+    config.user_deposit_cap = user_cap.map(|c| c.u128());
+    config_store.store(CONFIG_KEY, &config)?;
 
-    to_binary(&LPStakingQueryAnswer::ContractStatus {
-        is_stopped: config.is_stopped,
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&LPStakingHandleAnswer::SetDepositCaps {
+            status: Success,
+        })?),
     })
 }
 
-fn query_reward_token<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
-    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+fn set_deposit_allowlist<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    allowlist: Option<Vec<HumanAddr>>,
+) -> StdResult<HandleResponse> {
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: Config = config_store.load(CONFIG_KEY)?;
 
-    to_binary(&LPStakingQueryAnswer::RewardToken {
-        token: config.reward_token,
-    })
-}
+    enforce_admin(config.clone(), env)?;
 
-fn query_incentivized_token<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-) -> StdResult<Binary> {
-    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    config.deposit_allowlist = allowlist;
+    config_store.store(CONFIG_KEY, &config)?;
 
-    to_binary(&LPStakingQueryAnswer::IncentivizedToken {
-        token: config.inc_token,
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&LPStakingHandleAnswer::SetDepositAllowlist {
+            status: Success,
+        })?),
     })
 }
 
-// This is only for Keplr support (Viewing Keys)
-fn query_token_info<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
-    let token_info: TokenInfo = TypedStore::attach(&deps.storage).load(TOKEN_INFO_KEY)?;
+fn set_fees<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    fees: Fees,
+) -> StdResult<HandleResponse> {
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: Config = config_store.load(CONFIG_KEY)?;
 
-    to_binary(&LPStakingQueryAnswer::TokenInfo {
-        name: token_info.name,
-        symbol: token_info.symbol,
-        decimals: 1,
-        total_supply: None,
+    enforce_admin(config.clone(), env)?;
+
+    if fees.deposit_fee_bps as u128 > BPS_DENOMINATOR
+        || fees.withdraw_fee_bps as u128 > BPS_DENOMINATOR
+    {
+        return Err(StdError::generic_err(format!(
+            "fees cannot exceed {} basis points",
+            BPS_DENOMINATOR
+        )));
+    }
+    if (fees.deposit_fee_bps > 0 || fees.withdraw_fee_bps > 0) && fees.treasury.is_none() {
+        return Err(StdError::generic_err(
+            "a treasury must be set in order to charge fees",
+        ));
+    }
+
+    config.fees = fees;
+    config_store.store(CONFIG_KEY, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&LPStakingHandleAnswer::SetFees {
+            status: Success,
+        })?),
     })
 }
 
-// Helper functions
+fn set_vesting<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    vesting: Option<Vesting>,
+) -> StdResult<HandleResponse> {
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: Config = config_store.load(CONFIG_KEY)?;
 
-fn enforce_admin(config: Config, env: Env) -> StdResult<()> {
-    if config.admin != env.message.sender {
-        return Err(StdError::generic_err(format!(
-            "not an admin: {}",
-            env.message.sender
-        )));
+    enforce_admin(config.clone(), env)?;
+
+    if let Some(vesting) = &vesting {
+        if vesting.early_exit_penalty_bps as u128 > BPS_DENOMINATOR {
+            return Err(StdError::generic_err(format!(
+                "early exit penalty cannot exceed {} basis points",
+                BPS_DENOMINATOR
+            )));
+        }
     }
 
-    Ok(())
+    // Disabling vesting doesn't affect rewards that are already vesting
+    config.vesting = vesting;
+    config_store.store(CONFIG_KEY, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&LPStakingHandleAnswer::SetVesting {
+            status: Success,
+        })?),
+    })
 }
 
-fn update_rewards<S: Storage, A: Api, Q: Querier>(
+/// Pays out all of the sender's vesting rewards right away. The penalty on the unvested part goes
+/// back to the pool, and will be distributed to the stakers with the next allocation
+fn exit_vesting<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    newly_allocated: u128,
-) -> StdResult<RewardPool> {
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let address = env.message.sender;
+
+    let mut vesting_store = PrefixedStorage::new(VESTING_KEY, &mut deps.storage);
+    let mut schedule: VestingSchedule = TypedStore::attach(&vesting_store)
+        .load(address.0.as_bytes())
+        .unwrap_or_default(); // NotFound is the only possible error
+    schedule.release(env.block.height)?;
+
+    let penalty_bps = config
+        .vesting
+        .as_ref()
+        .map_or(0, |vesting| vesting.early_exit_penalty_bps);
+    let penalty = mul_div(schedule.unvested, penalty_bps as u128, BPS_DENOMINATOR)?;
+    let payout = sub(add(schedule.vested, schedule.unvested)?, penalty)?;
+    TypedStoreMut::attach(&mut vesting_store)
+        .store(address.0.as_bytes(), &VestingSchedule::default())?;
+
     let mut rewards_store = TypedStoreMut::attach(&mut deps.storage);
     let mut reward_pool: RewardPool = rewards_store.load(REWARD_POOL_KEY)?;
+    reward_pool.residue = add(reward_pool.residue, penalty)?;
+    reward_pool.rewards_owed = reward_pool.rewards_owed.saturating_sub(payout);
+    reward_pool.vesting_outstanding = reward_pool
+        .vesting_outstanding
+        .saturating_sub(add(schedule.vested, schedule.unvested)?);
+    rewards_store.store(REWARD_POOL_KEY, &reward_pool)?;
 
-    // If there's no new allocation - there is nothing to update because the state of the pool stays the same
-    if newly_allocated <= 0 {
-        return Ok(reward_pool);
+    let mut messages = vec![];
+    if payout > 0 {
+        store_tx(
+            &mut deps.storage,
+            &address,
+            TxAction::ClaimRewards,
+            payout,
+            env.block.height,
+            reward_pool.acc_reward_per_share,
+        )?;
+        messages.push(snip20::transfer_msg(
+            address,
+            Uint128(payout),
+            None,
+            RESPONSE_BLOCK_SIZE,
+            config.reward_token.contract_hash,
+            config.reward_token.address,
+        )?);
     }
 
-    if reward_pool.inc_token_supply == 0 {
-        reward_pool.residue += newly_allocated;
-        rewards_store.store(REWARD_POOL_KEY, &reward_pool)?;
-        return Ok(reward_pool);
-    }
+    Ok(HandleResponse {
+        messages,
+        log: vec![log("early_exit_penalty", penalty)],
+        data: Some(to_binary(&LPStakingHandleAnswer::ExitVesting {
+            status: Success,
+        })?),
+    })
+}
 
-    // Effectively distributes the residue to the first one that stakes to an empty pool
-    reward_pool.acc_reward_per_share +=
-        (newly_allocated + reward_pool.residue) * REWARD_SCALE / reward_pool.inc_token_supply;
-    reward_pool.residue = 0;
-    rewards_store.store(REWARD_POOL_KEY, &reward_pool)?;
+/// Lets stakers move their stakes to `new_contract`. New deposits are rejected from this point on
+fn migrate_to<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    new_contract: SecretContract,
+) -> StdResult<HandleResponse> {
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: Config = config_store.load(CONFIG_KEY)?;
 
-    Ok(reward_pool)
-}
+    enforce_admin(config.clone(), env)?;
+
+    if let Some(migration) = &config.migration {
+        return Err(StdError::generic_err(format!(
+            "this contract is already being migrated to {}",
+            migration.new_contract.address
+        )));
+    }
+
+    config.migration = Some(Migration {
+        new_contract,
+        migrated_users: 0,
+        migrated_amount: 0,
+    });
+    config_store.store(CONFIG_KEY, &config)?;
 
-fn update_allocation(env: Env, config: Config, hook: Option<Binary>) -> StdResult<HandleResponse> {
     Ok(HandleResponse {
-        messages: vec![WasmMsg::Execute {
-            contract_addr: config.master.address,
-            callback_code_hash: config.master.contract_hash,
-            msg: to_binary(&MasterHandleMsg::UpdateAllocation {
-                spy_addr: env.contract.address,
-                spy_hash: env.contract_code_hash,
-                hook,
-            })?,
-            send: vec![],
-        }
-        .into()],
+        messages: vec![],
         log: vec![],
-        data: None,
+        data: Some(to_binary(&LPStakingHandleAnswer::MigrateTo {
+            status: Success,
+        })?),
     })
 }
 
-#[cfg(test)]
+fn set_predecessor<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: Option<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: Config = config_store.load(CONFIG_KEY)?;
+
+    enforce_admin(config.clone(), env)?;
+
+    config.predecessor = address;
+    config_store.store(CONFIG_KEY, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&LPStakingHandleAnswer::SetPredecessor {
+            status: Success,
+        })?),
+    })
+}
+
+/// Sends every reward token the contract holds to the admin (or `to`). Stakers who didn't claim
+/// their rewards during the grace period forfeit them. Rewards that are already vesting are kept
+fn claim_reward_pool<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    to: Option<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    enforce_admin(config.clone(), env.clone())?;
+
+    let claim_block = config.deadline.saturating_add(CLAIM_GRACE_PERIOD);
+    if env.block.height <= claim_block {
+        return Err(StdError::generic_err(format!(
+            "minimum claim height hasn't passed yet: {}",
+            claim_block.saturating_add(1)
+        )));
+    }
+
+    let mut reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+
+    let balance = snip20::balance_query(
+        &deps.querier,
+        env.contract.address,
+        config.viewing_key,
+        RESPONSE_BLOCK_SIZE,
+        config.reward_token.contract_hash.clone(),
+        config.reward_token.address.clone(),
+    )?
+    .amount
+    .u128();
+
+    // Stakes are never part of the reward pool, even when staking the reward token itself
+    let amount = if config.reward_token.address == config.inc_token.address {
+        balance.saturating_sub(reward_pool.inc_token_supply)
+    } else {
+        balance
+    }
+    .saturating_sub(reward_pool.vesting_outstanding);
+
+    reward_pool.residue = 0;
+    reward_pool.rewards_owed = reward_pool.vesting_outstanding;
+    reward_pool.claimed_reward_per_share = reward_pool.acc_reward_per_share;
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+
+    let mut messages = vec![];
+    if amount > 0 {
+        messages.push(snip20::transfer_msg(
+            to.unwrap_or(env.message.sender),
+            Uint128(amount),
+            None,
+            RESPONSE_BLOCK_SIZE,
+            config.reward_token.contract_hash,
+            config.reward_token.address,
+        )?);
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![log("claimed_reward_pool", amount)],
+        data: Some(to_binary(&LPStakingHandleAnswer::ClaimRewardPool {
+            status: Success,
+        })?),
+    })
+}
+
+/// Rescues tokens that were sent to the contract by mistake. Stakes and rewards owed to stakers
+/// can never be touched
+fn recover_tokens<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    token: SecretContract,
+    amount: u128,
+    recipient: Option<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    enforce_admin(config.clone(), env.clone())?;
+
+    let is_inc_token = token.address == config.inc_token.address;
+    let is_reward_token = token.address == config.reward_token.address;
+    if is_inc_token || is_reward_token {
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+        let mut reserved = 0;
+        if is_inc_token {
+            reserved = add(reserved, reward_pool.inc_token_supply)?;
+        }
+        if is_reward_token {
+            reserved = add(reserved, reward_pool.rewards_owed)?;
+        }
+
+        let balance = snip20::balance_query(
+            &deps.querier,
+            env.contract.address,
+            config.viewing_key,
+            RESPONSE_BLOCK_SIZE,
+            token.contract_hash.clone(),
+            token.address.clone(),
+        )?
+        .amount
+        .u128();
+
+        let surplus = balance.saturating_sub(reserved);
+        if amount > surplus {
+            return Err(StdError::generic_err(format!(
+                "cannot recover more than the surplus: surplus={}, requested={}",
+                surplus, amount
+            )));
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![snip20::transfer_msg(
+            recipient.unwrap_or(env.message.sender),
+            Uint128(amount),
+            None,
+            RESPONSE_BLOCK_SIZE,
+            token.contract_hash,
+            token.address,
+        )?],
+        log: vec![log("recovered_tokens", amount)],
+        data: Some(to_binary(&LPStakingHandleAnswer::RecoverTokens {
+            status: Success,
+        })?),
+    })
+}
+
+fn set_residue_policy<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    policy: ResiduePolicy,
+) -> StdResult<HandleResponse> {
+    let mut config_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut config: Config = config_store.load(CONFIG_KEY)?;
+
+    enforce_admin(config.clone(), env)?;
+
+    if policy == ResiduePolicy::Treasury && config.fees.treasury.is_none() {
+        return Err(StdError::generic_err(
+            "a treasury must be set in order to send it the residue",
+        ));
+    }
+
+    // Residue that was already kept aside stays in the pool, and is released under the new policy
+    config.residue_policy = policy;
+    config_store.store(CONFIG_KEY, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&LPStakingHandleAnswer::SetResiduePolicy {
+            status: Success,
+        })?),
+    })
+}
+
+/// YOU SHOULD NEVER USE THIS! This will erase any eligibility for rewards you earned so far
+fn emergency_redeem<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let mut user: UserInfo = TypedStoreMut::attach(&mut deps.storage)
+        .load(env.message.sender.0.as_bytes())
+        .unwrap_or(UserInfo { locked: 0, debt: 0 });
+
+    let mut reward_pool: RewardPool =
+        TypedStoreMut::attach(&mut deps.storage).load(REWARD_POOL_KEY)?;
+
+    // Forfeited rewards go back to the pool, and will be distributed to the remaining stakers with
+    // the next allocation
+    let forfeited = get_pending(&user, &reward_pool)?;
+    reward_pool.residue = add(reward_pool.residue, forfeited)?;
+    reward_pool.inc_token_supply = sub(reward_pool.inc_token_supply, user.locked)?;
+    TypedStoreMut::attach(&mut deps.storage).store(REWARD_POOL_KEY, &reward_pool)?;
+
+    // Emergency redeems are only free of the withdrawal fee when the contract is stopped
+    let fee = if config.status == ContractStatusLevel::StopAll {
+        0
+    } else {
+        get_withdraw_fee(
+            &deps.storage,
+            &config,
+            &env.message.sender,
+            user.locked,
+            env.block.height,
+        )?
+    };
+
+    let mut messages = vec![];
+    if fee > 0 {
+        messages.push(fee_msg(&config.fees, &config.inc_token, fee)?);
+    }
+    if user.locked > 0 {
+        messages.push(secret_toolkit::snip20::transfer_msg(
+            env.message.sender.clone(),
+            Uint128(user.locked - fee),
+            None,
+            RESPONSE_BLOCK_SIZE,
+            config.inc_token.contract_hash,
+            config.inc_token.address,
+        )?);
+    }
+
+    if user.locked > 0 {
+        store_tx(
+            &mut deps.storage,
+            &env.message.sender,
+            TxAction::EmergencyRedeem,
+            user.locked,
+            env.block.height,
+            reward_pool.acc_reward_per_share,
+        )?;
+    }
+
+    user = UserInfo { locked: 0, debt: 0 };
+    TypedStoreMut::attach(&mut deps.storage).store(env.message.sender.0.as_bytes(), &user)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("forfeited_rewards", forfeited),
+            log("withdraw_fee", fee),
+        ],
+        data: Some(to_binary(&LPStakingHandleAnswer::EmergencyRedeem {
+            status: Success,
+        })?),
+    })
+}
+
+// Query functions
+
+fn query_pending_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    block: u64,
+    include_pending: bool,
+) -> StdResult<Binary> {
+    // If the master can't be queried, fall back to what was already credited to the pool
+    let new_rewards = if include_pending {
+        query_pending(deps, block).ok()
+    } else {
+        None
+    };
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let mut reward_pool =
+        TypedStore::<RewardPool, S>::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+    let user = TypedStore::<UserInfo, S>::attach(&deps.storage)
+        .load(address.0.as_bytes())
+        .unwrap_or(UserInfo { locked: 0, debt: 0 });
+
+    let includes_pending = new_rewards.is_some();
+    let new_rewards = get_credited_rewards(
+        &config,
+        &reward_pool,
+        new_rewards.unwrap_or_default(),
+        block,
+    )?;
+    if reward_pool.inc_token_supply != 0
+        && new_rewards > 0
+        && config.status != ContractStatusLevel::FreezeRewards
+    {
+        let residue = release_residue(&config, &mut reward_pool, block)?;
+        reward_pool.acc_reward_per_share = add(
+            reward_pool.acc_reward_per_share,
+            mul_div(
+                add(new_rewards, residue)?,
+                REWARD_SCALE,
+                reward_pool.inc_token_supply,
+            )?,
+        )?;
+    }
+
+    to_binary(&LPStakingQueryAnswer::Rewards {
+        // With pending rewards this is not necessarily accurate, since we don't validate
+        // new_rewards. It is up to the UI to display accurate numbers
+        rewards: Uint128(get_pending(&user, &reward_pool)?),
+        includes_pending,
+    })
+}
+
+fn query_deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let user = TypedStore::attach(&deps.storage)
+        .load(address.0.as_bytes())
+        .unwrap_or(UserInfo { locked: 0, debt: 0 });
+
+    to_binary(&LPStakingQueryAnswer::Balance {
+        amount: Uint128(user.locked),
+    })
+}
+
+fn query_history<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let txs = get_txs(&deps.storage, address, page, page_size)?;
+
+    to_binary(&LPStakingQueryAnswer::History { txs })
+}
+
+fn query_vesting<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    block: u64,
+) -> StdResult<Binary> {
+    let vesting_store = ReadonlyPrefixedStorage::new(VESTING_KEY, &deps.storage);
+    let mut schedule: VestingSchedule = TypedStore::attach(&vesting_store)
+        .load(address.0.as_bytes())
+        .unwrap_or_default();
+    schedule.release(block)?;
+
+    to_binary(&LPStakingQueryAnswer::Vesting {
+        vested: Uint128(schedule.vested),
+        unvested: Uint128(schedule.unvested),
+        end_block: schedule.end_block,
+    })
+}
+
+fn query_contract_status<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&LPStakingQueryAnswer::ContractStatus {
+        status: config.status,
+    })
+}
+
+fn query_reward_token<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&LPStakingQueryAnswer::RewardToken {
+        token: config.reward_token,
+    })
+}
+
+fn query_incentivized_token<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&LPStakingQueryAnswer::IncentivizedToken {
+        token: config.inc_token,
+    })
+}
+
+fn query_deadline<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&LPStakingQueryAnswer::Deadline {
+        block: config.deadline,
+    })
+}
+
+fn query_approved_depositors<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&LPStakingQueryAnswer::ApprovedDepositors {
+        depositors: config.approved_depositors,
+    })
+}
+
+fn query_pool_info<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+
+    to_binary(&LPStakingQueryAnswer::PoolInfo {
+        total_locked: Uint128(reward_pool.inc_token_supply),
+        acc_reward_per_share: Uint128(reward_pool.acc_reward_per_share),
+        residue: Uint128(reward_pool.residue),
+        last_reward_block: reward_pool.last_reward_block,
+        total_rewards_distributed: Uint128(reward_pool.total_rewards_distributed),
+    })
+}
+
+fn query_deposit_capacity<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+
+    let remaining = config
+        .total_deposit_cap
+        .map(|cap| Uint128(cap.saturating_sub(reward_pool.inc_token_supply)));
+
+    to_binary(&LPStakingQueryAnswer::DepositCapacity {
+        total_cap: config.total_deposit_cap.map(Uint128),
+        user_cap: config.user_deposit_cap.map(Uint128),
+        remaining,
+        allowlist: config.deposit_allowlist,
+    })
+}
+
+fn query_fees<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&LPStakingQueryAnswer::Fees { fees: config.fees })
+}
+
+fn query_vesting_config<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+
+    to_binary(&LPStakingQueryAnswer::VestingConfig {
+        vesting: config.vesting,
+    })
+}
+
+fn query_migration_status<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+
+    let (new_contract, migrated_users, migrated_amount) = match config.migration {
+        Some(migration) => (
+            Some(migration.new_contract),
+            migration.migrated_users,
+            migration.migrated_amount,
+        ),
+        None => (None, 0, 0),
+    };
+
+    to_binary(&LPStakingQueryAnswer::MigrationStatus {
+        new_contract,
+        migrated_users,
+        migrated_amount: Uint128(migrated_amount),
+        remaining_amount: Uint128(reward_pool.inc_token_supply),
+    })
+}
+
+fn query_residue_policy<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY)?;
+    let reward_pool: RewardPool = TypedStore::attach(&deps.storage).load(REWARD_POOL_KEY)?;
+
+    let stream_end_block = match reward_pool.residue_stream_end {
+        0 => None,
+        block => Some(block),
+    };
+
+    to_binary(&LPStakingQueryAnswer::ResiduePolicy {
+        policy: config.residue_policy,
+        residue: Uint128(reward_pool.residue),
+        stream_end_block,
+    })
+}
+
+// This is only for Keplr support (Viewing Keys)
+fn query_token_info<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let token_info: TokenInfo = TypedStore::attach(&deps.storage).load(TOKEN_INFO_KEY)?;
+
+    to_binary(&LPStakingQueryAnswer::TokenInfo {
+        name: token_info.name,
+        symbol: token_info.symbol,
+        decimals: 1,
+        total_supply: None,
+    })
+}
+
+// Helper functions
+
+fn enforce_admin(config: Config, env: Env) -> StdResult<()> {
+    if config.admin != env.message.sender {
+        return Err(StdError::generic_err(format!(
+            "not an admin: {}",
+            env.message.sender
+        )));
+    }
+
+    Ok(())
+}
+
+fn update_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    config: &Config,
+    newly_allocated: u128,
+) -> StdResult<(RewardPool, Vec<CosmosMsg>)> {
+    let mut rewards_store = TypedStoreMut::attach(&mut deps.storage);
+    let mut reward_pool: RewardPool = rewards_store.load(REWARD_POOL_KEY)?;
+
+    // If there's no new allocation - there is nothing to update because the state of the pool stays the same
+    if newly_allocated <= 0 {
+        return Ok((reward_pool, vec![]));
+    }
+
+    // Whatever isn't credited stays in the contract's balance, and can be reclaimed by the admin
+    let newly_allocated =
+        get_credited_rewards(config, &reward_pool, newly_allocated, env.block.height)?;
+    reward_pool.last_reward_block = env.block.height;
+
+    if newly_allocated == 0 {
+        rewards_store.store(REWARD_POOL_KEY, &reward_pool)?;
+        return Ok((reward_pool, vec![]));
+    }
+
+    // While rewards are frozen allocations are kept aside, and go to the stakers once unfrozen
+    if config.status == ContractStatusLevel::FreezeRewards {
+        reward_pool.residue = add(reward_pool.residue, newly_allocated)?;
+        reward_pool.rewards_owed = add(reward_pool.rewards_owed, newly_allocated)?;
+        rewards_store.store(REWARD_POOL_KEY, &reward_pool)?;
+        return Ok((reward_pool, vec![]));
+    }
+
+    if reward_pool.inc_token_supply == 0 {
+        let recipient = match config.residue_policy {
+            ResiduePolicy::Treasury => config.fees.treasury.as_ref().map(|t| t.address.clone()),
+            ResiduePolicy::ReturnToMaster => Some(config.master.address.clone()),
+            _ => None,
+        };
+
+        let mut messages = vec![];
+        if let Some(recipient) = recipient {
+            messages.push(snip20::transfer_msg(
+                recipient,
+                Uint128(newly_allocated),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                config.reward_token.contract_hash.clone(),
+                config.reward_token.address.clone(),
+            )?);
+        } else {
+            reward_pool.residue = add(reward_pool.residue, newly_allocated)?;
+            reward_pool.rewards_owed = add(reward_pool.rewards_owed, newly_allocated)?;
+        }
+        rewards_store.store(REWARD_POOL_KEY, &reward_pool)?;
+        return Ok((reward_pool, messages));
+    }
+    reward_pool.rewards_owed = add(reward_pool.rewards_owed, newly_allocated)?;
+
+    let residue = release_residue(config, &mut reward_pool, env.block.height)?;
+    let distributed = add(newly_allocated, residue)?;
+    reward_pool.acc_reward_per_share = add(
+        reward_pool.acc_reward_per_share,
+        mul_div(distributed, REWARD_SCALE, reward_pool.inc_token_supply)?,
+    )?;
+    reward_pool.total_rewards_distributed =
+        add(reward_pool.total_rewards_distributed, distributed)?;
+    rewards_store.store(REWARD_POOL_KEY, &reward_pool)?;
+
+    Ok((reward_pool, vec![]))
+}
+
+/// Takes the part of the residue that should be distributed to the stakers right now. Unless it's
+/// streamed, that's all of it
+fn release_residue(config: &Config, reward_pool: &mut RewardPool, block: u64) -> StdResult<u128> {
+    let released = match config.residue_policy {
+        ResiduePolicy::Stream { blocks } if blocks > 0 && reward_pool.residue > 0 => {
+            if reward_pool.residue_stream_end == 0 {
+                // Only start streaming once there's someone to stream to
+                reward_pool.residue_stream_start = block;
+                reward_pool.residue_stream_end = block.saturating_add(blocks);
+                0
+            } else if block >= reward_pool.residue_stream_end {
+                reward_pool.residue_stream_end = 0;
+                reward_pool.residue
+            } else if block <= reward_pool.residue_stream_start {
+                // Queries can ask about blocks from before the last release
+                0
+            } else {
+                let released = mul_div(
+                    reward_pool.residue,
+                    (block - reward_pool.residue_stream_start) as u128,
+                    (reward_pool.residue_stream_end - reward_pool.residue_stream_start) as u128,
+                )?;
+                reward_pool.residue_stream_start = block;
+                released
+            }
+        }
+        _ => {
+            reward_pool.residue_stream_end = 0;
+            reward_pool.residue
+        }
+    };
+    reward_pool.residue = sub(reward_pool.residue, released)?;
+
+    Ok(released)
+}
+
+/// Settles harvested rewards. With vesting enabled they go into the user's vesting schedule, and
+/// only what has vested so far is paid out. Returns the amount to transfer to the user
+fn harvest<S: Storage>(
+    storage: &mut S,
+    config: &Config,
+    reward_pool: &mut RewardPool,
+    address: &HumanAddr,
+    pending: u128,
+    block: u64,
+) -> StdResult<u128> {
+    let mut vesting_store = PrefixedStorage::new(VESTING_KEY, storage);
+    let schedule: Option<VestingSchedule> =
+        TypedStore::attach(&vesting_store).may_load(address.0.as_bytes())?;
+
+    let payout = if schedule.is_none() && config.vesting.is_none() {
+        pending
+    } else {
+        let mut schedule = schedule.unwrap_or_default();
+        let mut payout = pending;
+        match &config.vesting {
+            Some(vesting) if pending > 0 => {
+                schedule.add(pending, block, vesting.period)?;
+                reward_pool.vesting_outstanding = add(reward_pool.vesting_outstanding, pending)?;
+                payout = 0;
+            }
+            _ => schedule.release(block)?,
+        }
+        reward_pool.vesting_outstanding = reward_pool
+            .vesting_outstanding
+            .saturating_sub(schedule.vested);
+        payout = add(payout, schedule.vested)?;
+        schedule.vested = 0;
+        TypedStoreMut::attach(&mut vesting_store).store(address.0.as_bytes(), &schedule)?;
+
+        payout
+    };
+
+    // Rounding down the accumulator means `rewards_owed` may keep a few units of dust, which errs
+    // on the safe side
+    reward_pool.rewards_owed = reward_pool.rewards_owed.saturating_sub(payout);
+
+    Ok(payout)
+}
+
+fn get_fee(amount: u128, fee_bps: u16) -> StdResult<u128> {
+    mul_div(amount, fee_bps as u128, BPS_DENOMINATOR)
+}
+
+/// Redeeming within `withdraw_fee_period` blocks of the last deposit is charged the withdrawal fee
+fn get_withdraw_fee<S: ReadonlyStorage>(
+    storage: &S,
+    config: &Config,
+    address: &HumanAddr,
+    amount: u128,
+    block: u64,
+) -> StdResult<u128> {
+    let last_deposit_store = ReadonlyPrefixedStorage::new(LAST_DEPOSIT_KEY, storage);
+    let last_deposit: u64 = TypedStore::attach(&last_deposit_store)
+        .load(address.0.as_bytes())
+        .unwrap_or(0); // NotFound is the only possible error
+
+    if block < last_deposit.saturating_add(config.fees.withdraw_fee_period) {
+        get_fee(amount, config.fees.withdraw_fee_bps)
+    } else {
+        Ok(0)
+    }
+}
+
+fn fee_msg(fees: &Fees, inc_token: &SecretContract, fee: u128) -> StdResult<CosmosMsg> {
+    let treasury = fees
+        .treasury
+        .as_ref()
+        .ok_or_else(|| StdError::generic_err("fees are charged but no treasury is set"))?;
+
+    snip20::transfer_msg(
+        treasury.address.clone(),
+        Uint128(fee),
+        None,
+        RESPONSE_BLOCK_SIZE,
+        inc_token.contract_hash.clone(),
+        inc_token.address.clone(),
+    )
+}
+
+fn get_debt(locked: u128, acc_reward_per_share: u128) -> StdResult<u128> {
+    mul_div(locked, acc_reward_per_share, REWARD_SCALE)
+}
+
+fn get_pending(user: &UserInfo, reward_pool: &RewardPool) -> StdResult<u128> {
+    let accumulated = get_debt(user.locked, reward_pool.acc_reward_per_share)?;
+    // Rewards accumulated before the reward pool was reclaimed are forfeited
+    let forfeited = get_debt(user.locked, reward_pool.claimed_reward_per_share)?;
+
+    sub(accumulated, user.debt.max(forfeited))
+}
+
+/// Allocations are assumed to be spread evenly since the last allocation, so only the part that
+/// was allocated before the deadline is credited to stakers
+fn get_credited_rewards(
+    config: &Config,
+    reward_pool: &RewardPool,
+    newly_allocated: u128,
+    block: u64,
+) -> StdResult<u128> {
+    if block <= config.deadline {
+        return Ok(newly_allocated);
+    }
+    if reward_pool.last_reward_block >= config.deadline {
+        return Ok(0);
+    }
+
+    mul_div(
+        newly_allocated,
+        (config.deadline - reward_pool.last_reward_block) as u128,
+        (block - reward_pool.last_reward_block) as u128,
+    )
+}
+
+fn update_allocation(env: Env, config: Config, hook: Option<Binary>) -> StdResult<HandleResponse> {
+    Ok(HandleResponse {
+        messages: vec![WasmMsg::Execute {
+            contract_addr: config.master.address,
+            callback_code_hash: config.master.contract_hash,
+            msg: to_binary(&MasterHandleMsg::UpdateAllocation {
+                spy_addr: env.contract.address,
+                spy_hash: env.contract_code_hash,
+                hook,
+            })?,
+            send: vec![],
+        }
+        .into()],
+        log: vec![],
+        data: None,
+    })
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
-    use crate::msg::LPStakingHandleMsg::{Receive, Redeem, SetViewingKey};
-    use crate::msg::LPStakingQueryMsg::{Deposit, Rewards};
-    use crate::msg::LPStakingReceiveMsg;
-    use crate::state::SecretContract;
     use cosmwasm_std::testing::{
         mock_dependencies, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
     };
-    use cosmwasm_std::{
-        coins, from_binary, BlockInfo, Coin, ContractInfo, Empty, MessageInfo, StdError, WasmMsg,
-    };
+    use cosmwasm_std::{BlockInfo, Coin, ContractInfo, MessageInfo, QuerierResult};
     use rand::Rng;
-    use scrt_finance::lp_staking_msg::LPStakingHandleAnswer;
-    use scrt_finance::master_msg::LPStakingHandleMsg::SetViewingKey;
+    use scrt_finance::permit::{PermitParams, PermitSignature, PubKey};
     use serde::{Deserialize, Serialize};
 
-    // Helper functions
+    // Helper functions
+
+    fn init_helper(deadline: u64) -> Extern<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("admin", &[], 1);
+
+        let init_msg = LPStakingInitMsg {
+            reward_token: SecretContract {
+                address: HumanAddr("scrt".to_string()),
+                contract_hash: "1".to_string(),
+            },
+            inc_token: SecretContract {
+                address: HumanAddr("eth".to_string()),
+                contract_hash: "2".to_string(),
+            },
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            viewing_key: "123".to_string(),
+            master: SecretContract {
+                address: Default::default(),
+                contract_hash: "".to_string(),
+            },
+            token_info: TokenInfo {
+                name: "".to_string(),
+                symbol: "".to_string(),
+            },
+        };
+
+        init(&mut deps, env, init_msg).unwrap();
+        let deadline_msg = LPStakingHandleMsg::SetDeadline { block: deadline };
+        handle(&mut deps, mock_env("admin", &[], 1), deadline_msg).unwrap();
+
+        deps
+    }
+
+    /// Deposits `amount` for `user` the way the master does, through an allocation hook.
+    fn deposit_helper<S: Storage, A: Api, Q: Querier>(
+        deps: &mut Extern<S, A, Q>,
+        user: &str,
+        amount: u128,
+        height: u64,
+    ) -> StdResult<HandleResponse> {
+        let deposit_msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(0),
+            hook: Some(
+                to_binary(&LPStakingHookMsg::Deposit {
+                    from: HumanAddr(user.to_string()),
+                    amount: Uint128(amount),
+                })
+                .unwrap(),
+            ),
+        };
+        handle(deps, mock_env("admin", &[], height), deposit_msg)
+    }
+
+    fn extract_error_msg<T>(result: StdResult<T>) -> String {
+        match result {
+            Ok(_) => panic!("expected an error"),
+            Err(StdError::GenericErr { msg, .. }) => msg,
+            Err(err) => panic!("unexpected error: {:?}", err),
+        }
+    }
+
+    /// Just set sender and sent funds for the message. The rest uses defaults.
+    /// The sender will be canonicalized internally to allow developers pasing in human readable senders.
+    /// This is intended for use in test code only.
+    pub fn mock_env<U: Into<HumanAddr>>(sender: U, sent: &[Coin], height: u64) -> Env {
+        Env {
+            block: BlockInfo {
+                height,
+                time: 1_571_797_419,
+                chain_id: "cosmos-testnet-14002".to_string(),
+            },
+            message: MessageInfo {
+                sender: sender.into(),
+                sent_funds: sent.to_vec(),
+            },
+            contract: ContractInfo {
+                address: HumanAddr::from(MOCK_CONTRACT_ADDR),
+            },
+            contract_key: Some("".to_string()),
+            contract_code_hash: "".to_string(),
+        }
+    }
+
+    fn hook_from_action(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+        action: &str,
+        user: HumanAddr,
+    ) -> LPStakingHookMsg {
+        let mut rng = rand::thread_rng();
+
+        match action {
+            "deposit" => LPStakingHookMsg::Deposit {
+                from: user,
+                amount: Uint128(rng.gen_range(1e6 as u128, 1e12 as u128)),
+            },
+            "redeem" => {
+                let locked = TypedStore::<UserInfo, MockStorage>::attach(&deps.storage)
+                    .load(user.0.as_bytes())
+                    .map_or(0, |user| user.locked);
+
+                LPStakingHookMsg::Redeem {
+                    to: user,
+                    amount: Some(Uint128(rng.gen_range(0, locked + 1))),
+                }
+            }
+            _ => LPStakingHookMsg::Claim {
+                from: user.clone(),
+                recipient: user,
+                msg: None,
+            },
+        }
+    }
+
+    /// Plays the master's part: delivers `hook` together with the rewards allocated at `rate` per
+    /// block since the previous allocation. Returns the rewards paid out, and how much of the
+    /// allocation the pool should have credited
+    fn allocate_with_hook(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        hook: LPStakingHookMsg,
+        block: u64,
+        deadline: u64,
+        rate: u128,
+        last_allocation: &mut u64,
+    ) -> (u128, u128) {
+        let allocation = rate * (block - *last_allocation) as u128;
+        // Allocations are spread evenly over the blocks since the previous one, so only the part
+        // up to the deadline is credited
+        let credited = rate * block.min(deadline).saturating_sub(*last_allocation) as u128;
+        *last_allocation = block;
+
+        let msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(allocation),
+            hook: Some(to_binary(&hook).unwrap()),
+        };
+        let response = handle(deps, mock_env("admin", &[], block), msg).unwrap();
+
+        let mut paid = 0;
+        for message in response.messages {
+            if let CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr, msg, ..
+            }) = message
+            {
+                if contract_addr == HumanAddr("scrt".to_string()) {
+                    match from_binary::<Snip20HandleMsg>(&msg).unwrap() {
+                        Snip20HandleMsg::Transfer { amount, .. } => paid += amount.u128(),
+                    }
+                }
+            }
+        }
+
+        (paid, credited)
+    }
+
+    /// Runs random actions from `start_block` until a while after `deadline` and redeems everyone
+    /// at the end. What the users were paid must match what the pool credited, up to rounding
+    fn simulate_period(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        users: &[HumanAddr],
+        start_block: u64,
+        deadline: u64,
+        rate: u128,
+        last_allocation: &mut u64,
+    ) {
+        let mut rng = rand::thread_rng();
+        let actions = vec!["deposit", "redeem", "claim"];
+
+        let mut total_rewards_output = 0;
+        let mut total_credited = 0;
+        let mut num_of_actions = 0;
+
+        let end_block = deadline + 10_000;
+        for block in start_block..end_block {
+            for _ in 0..rng.gen_range(0, 5) {
+                let action = actions[rng.gen_range(0, actions.len())];
+                let user = users[rng.gen_range(0, users.len())].clone();
+
+                let hook = hook_from_action(deps, action, user);
+                let (paid, credited) =
+                    allocate_with_hook(deps, hook, block, deadline, rate, last_allocation);
+                total_rewards_output += paid;
+                total_credited += credited;
+                num_of_actions += 1;
+            }
+        }
+
+        // Make sure all users are fully redeemed
+        for user in users {
+            let hook = LPStakingHookMsg::Redeem {
+                to: user.clone(),
+                amount: None,
+            };
+            let (paid, credited) =
+                allocate_with_hook(deps, hook, end_block, deadline, rate, last_allocation);
+            total_rewards_output += paid;
+            total_credited += credited;
+            num_of_actions += 1;
+        }
+
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.inc_token_supply, 0);
+
+        // Rounding each payout down may pay up to a unit more than the exact share, but nothing
+        // beyond that
+        assert!(total_rewards_output <= total_credited + num_of_actions);
+        let error = 1.0 - (total_rewards_output as f64 / total_credited as f64);
+        assert!(error < 0.01, "error is {}", error);
+    }
+
+    fn sanity_run(rate: u128, deadline: u64) {
+        let mut deps = init_helper(deadline);
+
+        let users = vec![
+            HumanAddr("Lebron James".to_string()),
+            HumanAddr("Kobe Bryant".to_string()),
+            HumanAddr("Giannis".to_string()),
+            HumanAddr("Steph Curry".to_string()),
+            HumanAddr("Deni Avdija".to_string()),
+        ];
+
+        // The pool's last allocation is at init
+        let mut last_allocation = 1;
+        simulate_period(&mut deps, &users, 2, deadline, rate, &mut last_allocation);
+
+        // Do another run after first iteration is ended
+        let start_block = last_allocation + 1;
+        let new_deadline = start_block + deadline;
+        let msg = LPStakingHandleMsg::SetDeadline {
+            block: new_deadline,
+        };
+        handle(&mut deps, mock_env("admin", &[], start_block), msg).unwrap();
+        simulate_period(
+            &mut deps,
+            &users,
+            start_block,
+            new_deadline,
+            rate,
+            &mut last_allocation,
+        );
+    }
+
+    // Tests
+
+    #[test]
+    fn test_claim_pool() {
+        // Claim height is deadline + grace period + 1
+        let mut deps = init_helper(10000000);
+        let claim_height = 10000000 + CLAIM_GRACE_PERIOD + 1;
+
+        let claim_msg = LPStakingHandleMsg::ClaimRewardPool { to: None };
+        let handle_response = handle(&mut deps, mock_env("not_admin", &[], 10), claim_msg.clone());
+        assert_eq!(
+            extract_error_msg(handle_response),
+            "not an admin: not_admin".to_string()
+        );
+
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 10), claim_msg.clone());
+        assert_eq!(
+            extract_error_msg(handle_response),
+            format!("minimum claim height hasn't passed yet: {}", claim_height)
+        );
+
+        let handle_response = handle(
+            &mut deps,
+            mock_env("admin", &[], claim_height - 1),
+            claim_msg.clone(),
+        );
+        assert!(handle_response
+            .unwrap_err()
+            .to_string()
+            .contains("minimum claim height hasn't passed yet"));
+
+        let handle_response = handle(
+            &mut deps,
+            mock_env("admin", &[], claim_height),
+            claim_msg.clone(),
+        );
+        assert_eq!(extract_error_msg(handle_response), "Error performing Balance query: Generic error: Querier system error: No such contract: scrt".to_string());
+    }
+
+    #[test]
+    fn test_claim_pool_forfeits_unclaimed_rewards() {
+        let deps = init_helper(10);
+        let mut deps = Extern {
+            storage: deps.storage,
+            api: deps.api,
+            querier: BalanceQuerier { balance: 100 },
+        };
+
+        deposit_helper(&mut deps, "alice", 1000, 2).unwrap();
+        let allocation_msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(100),
+            hook: None,
+        };
+        handle(&mut deps, mock_env("admin", &[], 6), allocation_msg).unwrap();
+
+        let claim_msg = LPStakingHandleMsg::ClaimRewardPool {
+            to: Some(HumanAddr("treasury".to_string())),
+        };
+        let claim_height = 10 + CLAIM_GRACE_PERIOD + 1;
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[], claim_height - 1),
+            claim_msg.clone(),
+        );
+        assert!(handle_result.is_err());
+
+        let handle_response =
+            handle(&mut deps, mock_env("admin", &[], claim_height), claim_msg).unwrap();
+        assert_eq!(handle_response.log, vec![log("claimed_reward_pool", 100)]);
+        assert_eq!(
+            handle_response.messages,
+            vec![snip20::transfer_msg(
+                HumanAddr("treasury".to_string()),
+                Uint128(100),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                "1".to_string(),
+                HumanAddr("scrt".to_string()),
+            )
+            .unwrap()]
+        );
+
+        // Alice didn't claim within the grace period, so her rewards went with the pool
+        let user: UserInfo = TypedStore::attach(&deps.storage)
+            .load("alice".as_bytes())
+            .unwrap();
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(get_pending(&user, &reward_pool).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_claim_pool_keeps_vesting_rewards() {
+        let deps = init_helper(10);
+        let mut deps = Extern {
+            storage: deps.storage,
+            api: deps.api,
+            querier: BalanceQuerier { balance: 1000 },
+        };
+
+        let vesting_msg = LPStakingHandleMsg::SetVesting {
+            vesting: Some(Vesting {
+                period: 1_000_000,
+                early_exit_penalty_bps: 0,
+            }),
+        };
+        handle(&mut deps, mock_env("admin", &[], 1), vesting_msg).unwrap();
+
+        deposit_helper(&mut deps, "alice", 1000, 2).unwrap();
+        deposit_helper(&mut deps, "bob", 1000, 2).unwrap();
+
+        // Alice's half starts vesting, and keeps vesting long after the deadline
+        let claim_msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(1000),
+            hook: Some(
+                to_binary(&LPStakingHookMsg::Claim {
+                    from: HumanAddr("alice".to_string()),
+                    recipient: HumanAddr("alice".to_string()),
+                    msg: None,
+                })
+                .unwrap(),
+            ),
+        };
+        handle(&mut deps, mock_env("admin", &[], 6), claim_msg).unwrap();
+
+        // Only Bob's unclaimed half goes with the pool
+        let claim_height = 10 + CLAIM_GRACE_PERIOD + 1;
+        let claim_msg = LPStakingHandleMsg::ClaimRewardPool { to: None };
+        let handle_response =
+            handle(&mut deps, mock_env("admin", &[], claim_height), claim_msg).unwrap();
+        assert_eq!(handle_response.log, vec![log("claimed_reward_pool", 500)]);
+
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.rewards_owed, 500);
+        assert_eq!(reward_pool.vesting_outstanding, 500);
+
+        let exit_msg = LPStakingHandleMsg::ExitVesting {};
+        let handle_response =
+            handle(&mut deps, mock_env("alice", &[], claim_height), exit_msg).unwrap();
+        assert_eq!(
+            handle_response.messages[0],
+            snip20::transfer_msg(
+                HumanAddr("alice".to_string()),
+                Uint128(500),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                "1".to_string(),
+                HumanAddr("scrt".to_string()),
+            )
+            .unwrap()
+        );
+
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.rewards_owed, 0);
+        assert_eq!(reward_pool.vesting_outstanding, 0);
+    }
+
+    #[test]
+    fn test_set_deadline() {
+        let mut deps = init_helper(10000000);
+
+        let deadline_msg = LPStakingHandleMsg::SetDeadline { block: 100 };
+        let handle_result = handle(&mut deps, mock_env("alice", &[], 10), deadline_msg.clone());
+        assert_eq!(
+            extract_error_msg(handle_result),
+            "not an admin: alice".to_string()
+        );
 
-    fn init_helper(
-        deadline: u64,
-    ) -> (
-        StdResult<InitResponse>,
-        Extern<MockStorage, MockApi, MockQuerier>,
-    ) {
-        let mut deps = mock_dependencies(20, &[]);
-        let env = mock_env("admin", &[], 1);
+        let handle_result = handle(&mut deps, mock_env("admin", &[], 101), deadline_msg.clone());
+        assert!(handle_result
+            .unwrap_err()
+            .to_string()
+            .contains("deadline can't be in the past"));
 
-        let init_msg = LPStakingInitMsg {
-            reward_token: SecretContract {
-                address: HumanAddr("scrt".to_string()),
-                contract_hash: "1".to_string(),
-            },
-            inc_token: SecretContract {
-                address: HumanAddr("eth".to_string()),
-                contract_hash: "2".to_string(),
-            },
-            prng_seed: Binary::from("lolz fun yay".as_bytes()),
-            viewing_key: "123".to_string(),
-            master: SecretContract {
-                address: Default::default(),
-                contract_hash: "".to_string(),
-            },
-            token_info: TokenInfo {
-                name: "".to_string(),
-                symbol: "".to_string(),
-            },
+        handle(&mut deps, mock_env("admin", &[], 10), deadline_msg).unwrap();
+        match from_binary(&query(&deps, LPStakingQueryMsg::Deadline {}).unwrap()).unwrap() {
+            LPStakingQueryAnswer::Deadline { block } => assert_eq!(block, 100),
+            _ => panic!("Unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_rewards_prorated_at_deadline() {
+        let mut deps = init_helper(10);
+
+        deposit_helper(&mut deps, "alice", 1000, 2).unwrap();
+        let allocate = |amount: u128| LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(amount),
+            hook: None,
         };
 
-        (init(&mut deps, env, init_msg), deps)
+        // Fully before the deadline
+        handle(&mut deps, mock_env("admin", &[], 6), allocate(100)).unwrap();
+        // Half of the blocks since the last allocation are before the deadline
+        handle(&mut deps, mock_env("admin", &[], 14), allocate(100)).unwrap();
+        // Fully after the deadline
+        handle(&mut deps, mock_env("admin", &[], 20), allocate(100)).unwrap();
+
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.total_rewards_distributed, 150);
+        assert_eq!(reward_pool.rewards_owed, 150);
+        assert_eq!(reward_pool.last_reward_block, 20);
     }
 
-    /// Just set sender and sent funds for the message. The rest uses defaults.
-    /// The sender will be canonicalized internally to allow developers pasing in human readable senders.
-    /// This is intended for use in test code only.
-    pub fn mock_env<U: Into<HumanAddr>>(sender: U, sent: &[Coin], height: u64) -> Env {
-        Env {
-            block: BlockInfo {
-                height,
-                time: 1_571_797_419,
-                chain_id: "cosmos-testnet-14002".to_string(),
-            },
-            message: MessageInfo {
-                sender: sender.into(),
-                sent_funds: sent.to_vec(),
-            },
-            contract: ContractInfo {
-                address: HumanAddr::from(MOCK_CONTRACT_ADDR),
-            },
-            contract_key: Some("".to_string()),
-            contract_code_hash: "".to_string(),
-        }
+    #[test]
+    fn test_stop_contract() {
+        let mut deps = init_helper(10000000);
+
+        let stop_msg = LPStakingHandleMsg::StopContract {};
+        let handle_response = handle(&mut deps, mock_env("not_admin", &[], 10), stop_msg.clone());
+        assert_eq!(
+            extract_error_msg(handle_response),
+            "not an admin: not_admin".to_string()
+        );
+
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 10), stop_msg);
+        let unwrapped_result: LPStakingHandleAnswer =
+            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&LPStakingHandleAnswer::StopContract { status: Success }).unwrap()
+        );
+
+        let redeem_msg = LPStakingHandleMsg::Redeem { amount: None };
+        let handle_response = handle(&mut deps, mock_env("user", &[], 20), redeem_msg);
+        assert_eq!(
+            extract_error_msg(handle_response),
+            "this contract is stopped and this action is not allowed".to_string()
+        );
+
+        let resume_msg = LPStakingHandleMsg::ResumeContract {};
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 21), resume_msg);
+        let unwrapped_result: LPStakingHandleAnswer =
+            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&LPStakingHandleAnswer::ResumeContract { status: Success }).unwrap()
+        );
+
+        // The redeem is passed on to the master again
+        let redeem_msg = LPStakingHandleMsg::Redeem { amount: None };
+        let handle_response = handle(&mut deps, mock_env("user", &[], 20), redeem_msg).unwrap();
+        assert_eq!(handle_response.messages.len(), 1);
     }
 
-    fn msg_from_action<S: Storage, A: Api, Q: Querier>(
-        deps: &Extern<S, A, Q>,
-        action: &str,
-        user: HumanAddr,
-    ) -> (LPStakingHandleMsg, String) {
-        let mut rng = rand::thread_rng();
-        let chance = rng.gen_range(0, 100000);
+    #[test]
+    fn test_admin() {
+        let mut deps = init_helper(10000000);
 
-        match action {
-            "deposit" => {
-                let amount: u128 = rng.gen_range(10e12 as u128, 1000e18 as u128);
+        let admin_action_msg = LPStakingHandleMsg::ChangeAdmin {
+            address: HumanAddr("not_admin".to_string()),
+        };
+        let handle_response = handle(&mut deps, mock_env("not_admin", &[], 1), admin_action_msg);
+        assert_eq!(
+            extract_error_msg(handle_response),
+            "not an admin: not_admin".to_string()
+        );
 
-                let msg = LPStakingHandleMsg::Receive {
-                    sender: user.clone(),
-                    from: user,
-                    amount: Uint128(amount),
-                    msg: to_binary(&LPStakingReceiveMsg::Deposit {}).unwrap(),
-                };
+        let admin_action_msg = LPStakingHandleMsg::ChangeAdmin {
+            address: HumanAddr("new_admin".to_string()),
+        };
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 1), admin_action_msg);
+        let unwrapped_result: LPStakingHandleAnswer =
+            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&LPStakingHandleAnswer::ChangeAdmin { status: Success }).unwrap()
+        );
+
+        let admin_action_msg = LPStakingHandleMsg::ChangeAdmin {
+            address: HumanAddr("not_admin".to_string()),
+        };
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 1), admin_action_msg);
+        assert_eq!(
+            extract_error_msg(handle_response),
+            "not an admin: admin".to_string()
+        );
+
+        let admin_action_msg = LPStakingHandleMsg::ChangeAdmin {
+            address: HumanAddr("not_admin".to_string()),
+        };
+        let handle_response = handle(&mut deps, mock_env("new_admin", &[], 1), admin_action_msg);
+        let unwrapped_result: LPStakingHandleAnswer =
+            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&LPStakingHandleAnswer::ChangeAdmin { status: Success }).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_claim() {
+        let mut deps = init_helper(10000000);
+
+        let claim_msg = LPStakingHandleMsg::Claim {
+            recipient: None,
+            msg: None,
+        };
+        let handle_response = handle(&mut deps, mock_env("alice", &[], 2), claim_msg).unwrap();
+        assert_eq!(
+            handle_response.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::default(),
+                callback_code_hash: "".to_string(),
+                msg: to_binary(&MasterHandleMsg::UpdateAllocation {
+                    spy_addr: HumanAddr(MOCK_CONTRACT_ADDR.to_string()),
+                    spy_hash: "".to_string(),
+                    hook: Some(
+                        to_binary(&LPStakingHookMsg::Claim {
+                            from: HumanAddr("alice".to_string()),
+                            recipient: HumanAddr("alice".to_string()),
+                            msg: None,
+                        })
+                        .unwrap()
+                    ),
+                })
+                .unwrap(),
+                send: vec![],
+            })]
+        );
 
-                (msg, "eth".to_string())
+        deposit_helper(&mut deps, "alice", 1000, 2).unwrap();
+        let claim_hook = |amount: u128, recipient: &str, msg: Option<Binary>| {
+            LPStakingHandleMsg::NotifyAllocation {
+                amount: Uint128(amount),
+                hook: Some(
+                    to_binary(&LPStakingHookMsg::Claim {
+                        from: HumanAddr("alice".to_string()),
+                        recipient: HumanAddr(recipient.to_string()),
+                        msg,
+                    })
+                    .unwrap(),
+                ),
             }
-            "redeem" => {
-                let amount: u128 = rng.gen_range(1e12 as u128, 1000e18 as u128);
+        };
 
-                let msg = LPStakingHandleMsg::Redeem {
-                    amount: Some(Uint128(amount)),
-                };
+        // Without a message the rewards are transferred
+        let handle_response = handle(
+            &mut deps,
+            mock_env("admin", &[], 3),
+            claim_hook(500, "bob", None),
+        )
+        .unwrap();
+        assert_eq!(
+            handle_response.messages,
+            vec![snip20::transfer_msg(
+                HumanAddr("bob".to_string()),
+                Uint128(500),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                "1".to_string(),
+                HumanAddr("scrt".to_string()),
+            )
+            .unwrap()]
+        );
+
+        // With a message they are sent, so the recipient contract is notified
+        let router_msg = Binary::from("stake".as_bytes());
+        let handle_response = handle(
+            &mut deps,
+            mock_env("admin", &[], 4),
+            claim_hook(300, "router", Some(router_msg.clone())),
+        )
+        .unwrap();
+        assert_eq!(
+            handle_response.messages,
+            vec![snip20::send_msg(
+                HumanAddr("router".to_string()),
+                Uint128(300),
+                Some(router_msg),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                "1".to_string(),
+                HumanAddr("scrt".to_string()),
+            )
+            .unwrap()]
+        );
+
+        // Nothing left to claim
+        let handle_response = handle(
+            &mut deps,
+            mock_env("admin", &[], 5),
+            claim_hook(0, "bob", None),
+        )
+        .unwrap();
+        assert!(handle_response.messages.is_empty());
+    }
+
+    #[test]
+    fn test_emergency_redeem_forfeits_rewards() {
+        let mut deps = init_helper(10000000);
+
+        for user in vec!["alice", "bob"] {
+            deposit_helper(&mut deps, user, 1000, 2).unwrap();
+        }
+
+        let allocation_msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(2000),
+            hook: None,
+        };
+        handle(&mut deps, mock_env("admin", &[], 3), allocation_msg).unwrap();
+
+        let redeem_msg = LPStakingHandleMsg::EmergencyRedeem {};
+        let handle_response = handle(&mut deps, mock_env("alice", &[], 4), redeem_msg).unwrap();
+        assert_eq!(
+            handle_response.log,
+            vec![log("forfeited_rewards", 1000), log("withdraw_fee", 0)]
+        );
+
+        let reward_pool = TypedStore::<RewardPool, MockStorage>::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.residue, 1000);
+        assert_eq!(reward_pool.inc_token_supply, 1000);
+    }
+
+    #[test]
+    fn test_history() {
+        let mut deps = init_helper(10000000);
+
+        deposit_helper(&mut deps, "alice", 1000, 2).unwrap();
+
+        let redeem_msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(500),
+            hook: Some(
+                to_binary(&LPStakingHookMsg::Redeem {
+                    to: HumanAddr("alice".to_string()),
+                    amount: Some(Uint128(400)),
+                })
+                .unwrap(),
+            ),
+        };
+        handle(&mut deps, mock_env("admin", &[], 3), redeem_msg).unwrap();
+
+        let redeem_msg = LPStakingHandleMsg::EmergencyRedeem {};
+        handle(&mut deps, mock_env("alice", &[], 4), redeem_msg).unwrap();
+
+        let vk_msg = LPStakingHandleMsg::SetViewingKey {
+            key: "42".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[], 5), vk_msg).unwrap();
+
+        let query_msg = LPStakingQueryMsg::History {
+            address: HumanAddr("alice".to_string()),
+            key: "42".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let txs = match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            LPStakingQueryAnswer::History { txs } => txs,
+            _ => panic!("Unexpected"),
+        };
+
+        let summary: Vec<(TxAction, u128, u64)> = txs
+            .iter()
+            .map(|tx| (tx.action.clone(), tx.amount.u128(), tx.block_height))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (TxAction::EmergencyRedeem, 600, 4),
+                (TxAction::Redeem, 400, 3),
+                (TxAction::ClaimRewards, 500, 3),
+                (TxAction::Deposit, 1000, 2),
+            ]
+        );
+        assert_eq!(
+            txs[0].acc_reward_per_share,
+            Uint128(500 * REWARD_SCALE / 1000)
+        );
+        assert_eq!(txs[3].acc_reward_per_share, Uint128(0));
+
+        let query_msg = LPStakingQueryMsg::History {
+            address: HumanAddr("alice".to_string()),
+            key: "42".to_string(),
+            page: Some(1),
+            page_size: 3,
+        };
+        let txs = match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            LPStakingQueryAnswer::History { txs } => txs,
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0].action, TxAction::Deposit);
+
+        let query_msg = LPStakingQueryMsg::History {
+            address: HumanAddr("alice".to_string()),
+            key: "42".to_string(),
+            page: Some(u32::MAX),
+            page_size: 10,
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            LPStakingQueryAnswer::History { txs } => assert!(txs.is_empty()),
+            _ => panic!("Unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_pool_info() {
+        let mut deps = init_helper(10000000);
+
+        deposit_helper(&mut deps, "alice", 1000, 2).unwrap();
 
-                (msg, user.0)
+        let allocation_msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(500),
+            hook: None,
+        };
+        handle(&mut deps, mock_env("admin", &[], 3), allocation_msg).unwrap();
+
+        let query_result = query(&deps, LPStakingQueryMsg::PoolInfo {}).unwrap();
+        assert_eq!(query_result.len() % RESPONSE_BLOCK_SIZE, 0);
+        match from_binary(&query_result).unwrap() {
+            LPStakingQueryAnswer::PoolInfo {
+                total_locked,
+                acc_reward_per_share,
+                residue,
+                last_reward_block,
+                total_rewards_distributed,
+            } => {
+                assert_eq!(total_locked, Uint128(1000));
+                assert_eq!(acc_reward_per_share, Uint128(500 * REWARD_SCALE / 1000));
+                assert_eq!(residue, Uint128(0));
+                assert_eq!(last_reward_block, 3);
+                assert_eq!(total_rewards_distributed, Uint128(500));
             }
-            "deadline" if chance == 42 => {
-                let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY).unwrap();
-                let current = config.deadline as f64;
+            _ => panic!("Unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_deposit_limits() {
+        let mut deps = init_helper(10000000);
 
-                let new = rng.gen_range(current + 1.0, current * 1.001);
+        let caps_msg = LPStakingHandleMsg::SetDepositCaps {
+            total_cap: Some(Uint128(1500)),
+            user_cap: Some(Uint128(1000)),
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[], 2), caps_msg.clone());
+        assert!(handle_result.is_err());
+        handle(&mut deps, mock_env("admin", &[], 2), caps_msg).unwrap();
+
+        let allowlist_msg = LPStakingHandleMsg::SetDepositAllowlist {
+            allowlist: Some(vec![
+                HumanAddr("alice".to_string()),
+                HumanAddr("bob".to_string()),
+            ]),
+        };
+        handle(&mut deps, mock_env("admin", &[], 2), allowlist_msg).unwrap();
+
+        let handle_result = deposit_helper(&mut deps, "charlie", 10, 3);
+        assert!(handle_result
+            .unwrap_err()
+            .to_string()
+            .contains("not allowed to deposit"));
+
+        let handle_result = deposit_helper(&mut deps, "alice", 1001, 3);
+        assert!(handle_result
+            .unwrap_err()
+            .to_string()
+            .contains("per-user cap"));
+        deposit_helper(&mut deps, "alice", 1000, 3).unwrap();
+
+        let handle_result = deposit_helper(&mut deps, "bob", 501, 4);
+        assert!(handle_result.unwrap_err().to_string().contains("pool cap"));
+        deposit_helper(&mut deps, "bob", 300, 4).unwrap();
+
+        let query_result = query(&deps, LPStakingQueryMsg::DepositCapacity {}).unwrap();
+        match from_binary(&query_result).unwrap() {
+            LPStakingQueryAnswer::DepositCapacity {
+                total_cap,
+                user_cap,
+                remaining,
+                ..
+            } => {
+                assert_eq!(total_cap, Some(Uint128(1500)));
+                assert_eq!(user_cap, Some(Uint128(1000)));
+                assert_eq!(remaining, Some(Uint128(200)));
+            }
+            _ => panic!("Unexpected"),
+        }
+    }
 
-                let msg = LPStakingHandleMsg::SetDeadline { block: new as u64 };
+    #[test]
+    fn test_deposit_for() {
+        let mut deps = init_helper(10000000);
 
-                (msg, "admin".to_string())
+        let query_result = query(&deps, LPStakingQueryMsg::ApprovedDepositors {}).unwrap();
+        match from_binary(&query_result).unwrap() {
+            LPStakingQueryAnswer::ApprovedDepositors { depositors } => {
+                assert!(depositors.is_empty())
             }
-            "rewards" if chance == 7 => {
-                let amount: u128 = rng.gen_range(10000e6 as u128, 100000e6 as u128);
+            _ => panic!("Unexpected"),
+        }
 
-                let msg = LPStakingHandleMsg::Receive {
-                    sender: user.clone(),
-                    from: user,
-                    amount: Uint128(amount),
-                    msg: to_binary(&LPStakingReceiveMsg::DepositRewards {}).unwrap(),
-                };
+        let deposit_for_msg = LPStakingHandleMsg::Receive {
+            sender: HumanAddr("zap".to_string()),
+            from: HumanAddr("zap".to_string()),
+            amount: Uint128(1000),
+            msg: to_binary(&LPStakingReceiveMsg::DepositFor {
+                beneficiary: HumanAddr("alice".to_string()),
+            })
+            .unwrap(),
+        };
+        let handle_result = handle(&mut deps, mock_env("eth", &[], 2), deposit_for_msg.clone());
+        assert!(handle_result
+            .unwrap_err()
+            .to_string()
+            .contains("not an approved depositor: zap"));
+
+        let depositors_msg = LPStakingHandleMsg::SetApprovedDepositors {
+            depositors: vec![HumanAddr("zap".to_string())],
+        };
+        let handle_result = handle(&mut deps, mock_env("zap", &[], 2), depositors_msg.clone());
+        assert_eq!(
+            extract_error_msg(handle_result),
+            "not an admin: zap".to_string()
+        );
+        handle(&mut deps, mock_env("admin", &[], 2), depositors_msg).unwrap();
 
-                (msg, "scrt".to_string())
+        let query_result = query(&deps, LPStakingQueryMsg::ApprovedDepositors {}).unwrap();
+        match from_binary(&query_result).unwrap() {
+            LPStakingQueryAnswer::ApprovedDepositors { depositors } => {
+                assert_eq!(depositors, vec![HumanAddr("zap".to_string())])
             }
-            _ => (
-                LPStakingHandleMsg::Redeem {
-                    amount: Some(Uint128(u128::MAX)), // This will never work but will keep the tests going
-                },
-                "".to_string(),
-            ),
+            _ => panic!("Unexpected"),
         }
+
+        // The stake is credited to the beneficiary, not the depositor
+        let handle_response = handle(&mut deps, mock_env("eth", &[], 3), deposit_for_msg).unwrap();
+        assert_eq!(
+            handle_response.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::default(),
+                callback_code_hash: "".to_string(),
+                msg: to_binary(&MasterHandleMsg::UpdateAllocation {
+                    spy_addr: HumanAddr(MOCK_CONTRACT_ADDR.to_string()),
+                    spy_hash: "".to_string(),
+                    hook: Some(
+                        to_binary(&LPStakingHookMsg::DepositFor {
+                            beneficiary: HumanAddr("alice".to_string()),
+                            amount: Uint128(1000),
+                        })
+                        .unwrap()
+                    ),
+                })
+                .unwrap(),
+                send: vec![],
+            })]
+        );
     }
 
-    fn print_status(
-        deps: &Extern<MockStorage, MockApi, MockQuerier>,
-        users: Vec<HumanAddr>,
-        new_mint: u128,
-    ) {
-        let reward_pool = TypedStore::<RewardPool, MockStorage>::attach(&deps.storage)
+    #[test]
+    fn test_fees() {
+        let mut deps = init_helper(10000000);
+
+        let treasury = SecretContract {
+            address: HumanAddr("treasury".to_string()),
+            contract_hash: "3".to_string(),
+        };
+        let mut fees = Fees {
+            deposit_fee_bps: 100,
+            withdraw_fee_bps: 500,
+            withdraw_fee_period: 10,
+            treasury: None,
+        };
+        let fees_msg = LPStakingHandleMsg::SetFees { fees: fees.clone() };
+        let handle_result = handle(&mut deps, mock_env("admin", &[], 2), fees_msg);
+        assert!(handle_result.is_err());
+
+        fees.treasury = Some(treasury.clone());
+        let fees_msg = LPStakingHandleMsg::SetFees { fees: fees.clone() };
+        handle(&mut deps, mock_env("admin", &[], 2), fees_msg).unwrap();
+
+        let handle_response = deposit_helper(&mut deps, "alice", 1000, 3).unwrap();
+        assert_eq!(handle_response.log, vec![log("deposit_fee", 10)]);
+        assert_eq!(
+            handle_response.messages[0],
+            snip20::transfer_msg(
+                treasury.address.clone(),
+                Uint128(10),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                "2".to_string(),
+                HumanAddr("eth".to_string()),
+            )
+            .unwrap()
+        );
+
+        let user: UserInfo = TypedStore::attach(&deps.storage)
+            .load("alice".as_bytes())
+            .unwrap();
+        assert_eq!(user.locked, 990);
+
+        // Within the withdrawal fee period
+        let redeem = |amount: u128| LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(0),
+            hook: Some(
+                to_binary(&LPStakingHookMsg::Redeem {
+                    to: HumanAddr("alice".to_string()),
+                    amount: Some(Uint128(amount)),
+                })
+                .unwrap(),
+            ),
+        };
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 12), redeem(400)).unwrap();
+        assert_eq!(handle_response.log, vec![log("withdraw_fee", 20)]);
+
+        // After the withdrawal fee period
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 13), redeem(590)).unwrap();
+        assert_eq!(handle_response.log, vec![log("withdraw_fee", 0)]);
+
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
             .load(REWARD_POOL_KEY)
             .unwrap();
-        let config: Config = TypedStore::attach(&deps.storage).load(CONFIG_KEY).unwrap();
-
-        println!("####### Statistics for block: {} #######", block);
-        println!("Deadline: {}", config.deadline);
-        println!("Locked ETH: {}", reward_pool.inc_token_supply);
-        println!("Pending rewards: {}", reward_pool.pending_rewards);
-        println!(
-            "Accumulated rewards per share: {}",
-            reward_pool.acc_reward_per_share
-        );
-        println!("Last reward block: {}", reward_pool.last_reward_block);
+        assert_eq!(reward_pool.inc_token_supply, 0);
 
-        for user in users {
-            println!("## {}:", user.0);
-            let user_info = TypedStore::<UserInfo, MockStorage>::attach(&deps.storage)
-                .load(user.0.as_bytes())
-                .unwrap_or(UserInfo { locked: 0, debt: 0 });
-            let rewards = query_rewards(deps, user.clone(), Uint128(new_mint));
-
-            println!("Locked: {}", user_info.locked);
-            println!("Debt: {}", user_info.debt);
-            println!("Reward: {}", rewards);
+        let query_result = query(&deps, LPStakingQueryMsg::Fees {}).unwrap();
+        match from_binary(&query_result).unwrap() {
+            LPStakingQueryAnswer::Fees { fees: queried } => assert_eq!(queried, fees),
+            _ => panic!("Unexpected"),
         }
+    }
+
+    #[test]
+    fn test_withdraw_fee_period() {
+        let mut deps = init_helper(10000000);
+
+        let treasury = SecretContract {
+            address: HumanAddr("treasury".to_string()),
+            contract_hash: "3".to_string(),
+        };
+        let fees_msg = LPStakingHandleMsg::SetFees {
+            fees: Fees {
+                deposit_fee_bps: 0,
+                withdraw_fee_bps: 500,
+                withdraw_fee_period: 10,
+                treasury: Some(treasury.clone()),
+            },
+        };
+        handle(&mut deps, mock_env("admin", &[], 1), fees_msg).unwrap();
+
+        // Someone else depositing for alice doesn't restart her withdrawal fee period
+        deposit_helper(&mut deps, "alice", 1000, 2).unwrap();
+        let deposit_for_msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(0),
+            hook: Some(
+                to_binary(&LPStakingHookMsg::DepositFor {
+                    beneficiary: HumanAddr("alice".to_string()),
+                    amount: Uint128(1),
+                })
+                .unwrap(),
+            ),
+        };
+        handle(&mut deps, mock_env("admin", &[], 11), deposit_for_msg).unwrap();
+
+        let redeem_msg = LPStakingHandleMsg::EmergencyRedeem {};
+        let handle_response = handle(&mut deps, mock_env("alice", &[], 12), redeem_msg).unwrap();
+        assert_eq!(
+            handle_response.log,
+            vec![log("forfeited_rewards", 0), log("withdraw_fee", 0)]
+        );
 
-        println!();
+        // Emergency redeems are charged the withdrawal fee too
+        deposit_helper(&mut deps, "bob", 1000, 12).unwrap();
+        let redeem_msg = LPStakingHandleMsg::EmergencyRedeem {};
+        let handle_response = handle(&mut deps, mock_env("bob", &[], 13), redeem_msg).unwrap();
+        assert_eq!(
+            handle_response.messages,
+            vec![
+                snip20::transfer_msg(
+                    treasury.address,
+                    Uint128(50),
+                    None,
+                    RESPONSE_BLOCK_SIZE,
+                    "2".to_string(),
+                    HumanAddr("eth".to_string()),
+                )
+                .unwrap(),
+                snip20::transfer_msg(
+                    HumanAddr("bob".to_string()),
+                    Uint128(950),
+                    None,
+                    RESPONSE_BLOCK_SIZE,
+                    "2".to_string(),
+                    HumanAddr("eth".to_string()),
+                )
+                .unwrap(),
+            ]
+        );
     }
 
-    fn query_rewards(
-        deps: &Extern<MockStorage, MockApi, MockQuerier>,
-        user: HumanAddr,
-        new_mint: Uint128,
-    ) -> u128 {
-        let query_msg = LPStakingQueryMsg::Rewards {
-            address: user,
-            new_rewards: new_mint,
+    #[test]
+    fn test_vesting() {
+        let mut deps = init_helper(10000000);
+
+        let vesting_msg = LPStakingHandleMsg::SetVesting {
+            vesting: Some(Vesting {
+                period: 100,
+                early_exit_penalty_bps: 5000,
+            }),
+        };
+        handle(&mut deps, mock_env("admin", &[], 2), vesting_msg).unwrap();
+
+        deposit_helper(&mut deps, "alice", 1000, 2).unwrap();
+
+        // Harvested rewards start vesting instead of being paid out
+        let claim_msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(1000),
+            hook: Some(
+                to_binary(&LPStakingHookMsg::Claim {
+                    from: HumanAddr("alice".to_string()),
+                    recipient: HumanAddr("alice".to_string()),
+                    msg: None,
+                })
+                .unwrap(),
+            ),
+        };
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 10), claim_msg).unwrap();
+        assert!(handle_response.messages.is_empty());
+
+        let vk_msg = LPStakingHandleMsg::SetViewingKey {
             key: "42".to_string(),
+            padding: None,
         };
+        handle(&mut deps, mock_env("alice", &[], 10), vk_msg).unwrap();
 
-        let result: LPStakingQueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
-        match result {
-            LPStakingQueryAnswer::Rewards { rewards } => rewards.u128(),
-            _ => panic!("NOPE"),
+        let query_msg = LPStakingQueryMsg::Vesting {
+            address: HumanAddr("alice".to_string()),
+            key: "42".to_string(),
+            height: 60,
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            LPStakingQueryAnswer::Vesting {
+                vested,
+                unvested,
+                end_block,
+            } => {
+                assert_eq!(vested, Uint128(500));
+                assert_eq!(unvested, Uint128(500));
+                assert_eq!(end_block, 110);
+            }
+            _ => panic!("Unexpected"),
         }
-    }
 
-    fn set_vks(deps: &mut Extern<MockStorage, MockApi, MockQuerier>, users: Vec<HumanAddr>) {
-        for user in users {
-            let vk_msg = SetViewingKey {
-                key: "42".to_string(),
-                padding: None,
-            };
-            handle(deps, mock_env(user.0, &[], 2001), vk_msg).unwrap();
-        }
+        // Half of the unvested rewards are forfeited to the pool
+        let exit_msg = LPStakingHandleMsg::ExitVesting {};
+        let handle_response = handle(&mut deps, mock_env("alice", &[], 60), exit_msg).unwrap();
+        assert_eq!(handle_response.log, vec![log("early_exit_penalty", 250)]);
+        assert_eq!(
+            handle_response.messages[0],
+            snip20::transfer_msg(
+                HumanAddr("alice".to_string()),
+                Uint128(750),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                "1".to_string(),
+                HumanAddr("scrt".to_string()),
+            )
+            .unwrap()
+        );
+
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.residue, 250);
     }
 
-    fn extract_rewards(result: StdResult<HandleResponse>) -> u128 {
-        match result {
-            Ok(resp) => {
-                for message in resp.messages {
-                    match message {
-                        CosmosMsg::Wasm(w) => match w {
-                            WasmMsg::Execute {
-                                contract_addr, msg, ..
-                            } => {
-                                if contract_addr == HumanAddr("scrt".to_string()) {
-                                    let transfer_msg: Snip20HandleMsg = from_binary(&msg).unwrap();
-
-                                    match transfer_msg {
-                                        Snip20HandleMsg::Transfer { amount, .. } => {
-                                            return amount.u128();
-                                        }
-                                        _ => panic!(),
-                                    }
-                                }
-                            }
-                            _ => panic!(),
-                        },
-                        _ => panic!(),
-                    }
-                }
-            }
-            Err(e) => match e {
-                StdError::NotFound { .. } => {}
-                StdError::GenericErr { msg, backtrace } => {
-                    if !msg.contains("insufficient") {
-                        panic!(format!("{}", msg))
-                    }
-                }
-                _ => panic!(format!("{:?}", e)),
-            },
+    #[test]
+    fn test_migration() {
+        let mut deps = init_helper(10000000);
+
+        for (user, amount) in vec![("alice", 1000), ("bob", 500)] {
+            deposit_helper(&mut deps, user, amount, 2).unwrap();
         }
 
-        0
-    }
+        let new_contract = SecretContract {
+            address: HumanAddr("successor".to_string()),
+            contract_hash: "4".to_string(),
+        };
+        let migrate_to_msg = LPStakingHandleMsg::MigrateTo {
+            new_contract: new_contract.clone(),
+        };
+        handle(&mut deps, mock_env("admin", &[], 3), migrate_to_msg).unwrap();
 
-    fn extract_reward_deposit(msg: LPStakingHandleMsg) -> u128 {
-        match msg {
-            LPStakingHandleMsg::Receive { amount, msg, .. } => {
-                let transfer_msg: LPStakingReceiveMsg = from_binary(&msg).unwrap();
+        let deposit_msg = LPStakingHandleMsg::Receive {
+            sender: HumanAddr("alice".to_string()),
+            from: HumanAddr("alice".to_string()),
+            amount: Uint128(100),
+            msg: to_binary(&LPStakingReceiveMsg::Deposit {}).unwrap(),
+        };
+        let handle_result = handle(&mut deps, mock_env("eth", &[], 3), deposit_msg);
+        assert!(handle_result.unwrap_err().to_string().contains("migrated"));
+
+        let migrate_msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(0),
+            hook: Some(
+                to_binary(&LPStakingHookMsg::Migrate {
+                    users: vec![
+                        HumanAddr("alice".to_string()),
+                        HumanAddr("charlie".to_string()),
+                    ],
+                })
+                .unwrap(),
+            ),
+        };
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 4), migrate_msg).unwrap();
+        assert_eq!(handle_response.log, vec![log("migrated_users", 1)]);
+        assert_eq!(
+            handle_response.messages,
+            vec![snip20::send_msg(
+                new_contract.address.clone(),
+                Uint128(1000),
+                Some(
+                    to_binary(&LPStakingReceiveMsg::Migrate {
+                        beneficiary: HumanAddr("alice".to_string()),
+                    })
+                    .unwrap()
+                ),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                "2".to_string(),
+                HumanAddr("eth".to_string()),
+            )
+            .unwrap()]
+        );
 
-                match transfer_msg {
-                    LPStakingReceiveMsg::DepositRewards {} => amount.u128(),
-                    _ => 0,
-                }
+        let query_result = query(&deps, LPStakingQueryMsg::MigrationStatus {}).unwrap();
+        match from_binary(&query_result).unwrap() {
+            LPStakingQueryAnswer::MigrationStatus {
+                new_contract: queried,
+                migrated_users,
+                migrated_amount,
+                remaining_amount,
+            } => {
+                assert_eq!(queried, Some(new_contract));
+                assert_eq!(migrated_users, 1);
+                assert_eq!(migrated_amount, Uint128(1000));
+                assert_eq!(remaining_amount, Uint128(500));
             }
-            _ => 0,
+            _ => panic!("Unexpected"),
         }
     }
 
-    fn sanity_run(mut rewards: u128, mut deadline: u64) {
-        let mut rng = rand::thread_rng();
+    #[test]
+    fn test_receive_migrated() {
+        let mut deps = init_helper(10000000);
+
+        let migrated_msg = LPStakingHandleMsg::Receive {
+            sender: HumanAddr("predecessor".to_string()),
+            from: HumanAddr("predecessor".to_string()),
+            amount: Uint128(1000),
+            msg: to_binary(&LPStakingReceiveMsg::Migrate {
+                beneficiary: HumanAddr("alice".to_string()),
+            })
+            .unwrap(),
+        };
+        let handle_result = handle(&mut deps, mock_env("eth", &[], 2), migrated_msg.clone());
+        assert!(handle_result.is_err());
 
-        let (init_result, mut deps) = init_helper(deadline);
+        let predecessor_msg = LPStakingHandleMsg::SetPredecessor {
+            address: Some(HumanAddr("predecessor".to_string())),
+        };
+        handle(&mut deps, mock_env("admin", &[], 2), predecessor_msg).unwrap();
+
+        let handle_response =
+            handle(&mut deps, mock_env("eth", &[], 2), migrated_msg.clone()).unwrap();
+        assert_eq!(handle_response.messages.len(), 1);
+
+        // Migrated stakes already paid the predecessor's fees and passed its limits
+        let fees_msg = LPStakingHandleMsg::SetFees {
+            fees: Fees {
+                deposit_fee_bps: 100,
+                withdraw_fee_bps: 500,
+                withdraw_fee_period: 10,
+                treasury: Some(SecretContract {
+                    address: HumanAddr("treasury".to_string()),
+                    contract_hash: "3".to_string(),
+                }),
+            },
+        };
+        handle(&mut deps, mock_env("admin", &[], 3), fees_msg).unwrap();
+        let caps_msg = LPStakingHandleMsg::SetDepositCaps {
+            total_cap: Some(Uint128(500)),
+            user_cap: Some(Uint128(500)),
+        };
+        handle(&mut deps, mock_env("admin", &[], 3), caps_msg).unwrap();
+        let allowlist_msg = LPStakingHandleMsg::SetDepositAllowlist {
+            allowlist: Some(vec![HumanAddr("bob".to_string())]),
+        };
+        handle(&mut deps, mock_env("admin", &[], 3), allowlist_msg).unwrap();
+        let status_msg = LPStakingHandleMsg::SetContractStatus {
+            level: ContractStatusLevel::StopDeposits,
+        };
+        handle(&mut deps, mock_env("admin", &[], 3), status_msg).unwrap();
+
+        let handle_response = handle(&mut deps, mock_env("eth", &[], 4), migrated_msg).unwrap();
+        assert_eq!(handle_response.messages.len(), 1);
+
+        let migrated_hook = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(0),
+            hook: Some(
+                to_binary(&LPStakingHookMsg::ReceiveMigrated {
+                    beneficiary: HumanAddr("alice".to_string()),
+                    amount: Uint128(1000),
+                })
+                .unwrap(),
+            ),
+        };
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 4), migrated_hook).unwrap();
+        assert!(handle_response.messages.is_empty());
 
-        deposit_rewards(&mut deps, mock_env("scrt", &[], 1), rewards).unwrap();
+        let user: UserInfo = TypedStore::attach(&deps.storage)
+            .load("alice".as_bytes())
+            .unwrap();
+        assert_eq!(user.locked, 1000);
 
-        let actions = vec!["deposit", "redeem", "deadline", "rewards"];
-        let users = vec![
-            HumanAddr("Lebron James".to_string()),
-            HumanAddr("Kobe Bryant".to_string()),
-            HumanAddr("Giannis".to_string()),
-            HumanAddr("Steph Curry".to_string()),
-            HumanAddr("Deni Avdija".to_string()),
-        ];
+        // Nor do they restart the withdrawal fee period
+        let last_deposit_store = ReadonlyPrefixedStorage::new(LAST_DEPOSIT_KEY, &deps.storage);
+        let last_deposit: Option<u64> = TypedStore::attach(&last_deposit_store)
+            .may_load("alice".as_bytes())
+            .unwrap();
+        assert_eq!(last_deposit, None);
+    }
 
-        let mut total_rewards_output = 0;
+    #[test]
+    fn test_contract_status_levels() {
+        let mut deps = init_helper(10000000);
 
-        set_vks(&mut deps, users.clone());
-        let mut block: u64 = 2;
-        while block < (deadline + 10_000) {
-            let num_of_actions = rng.gen_range(0, 5);
+        deposit_helper(&mut deps, "alice", 1000, 2).unwrap();
 
-            for i in 0..num_of_actions {
-                let action_idx = rng.gen_range(0, actions.len());
+        let deposit_msg = LPStakingHandleMsg::Receive {
+            sender: HumanAddr("alice".to_string()),
+            from: HumanAddr("alice".to_string()),
+            amount: Uint128(100),
+            msg: to_binary(&LPStakingReceiveMsg::Deposit {}).unwrap(),
+        };
+        let set_status = |level| LPStakingHandleMsg::SetContractStatus { level };
 
-                let user_idx = rng.gen_range(0, users.len());
-                let user = users[user_idx].clone();
+        // Deposits paused, redeems still work
+        handle(
+            &mut deps,
+            mock_env("admin", &[], 3),
+            set_status(ContractStatusLevel::StopDeposits),
+        )
+        .unwrap();
+        let handle_result = handle(&mut deps, mock_env("eth", &[], 3), deposit_msg.clone());
+        assert!(handle_result.unwrap_err().to_string().contains("paused"));
+        let redeem_msg = LPStakingHandleMsg::Redeem {
+            amount: Some(Uint128(100)),
+        };
+        handle(&mut deps, mock_env("alice", &[], 3), redeem_msg).unwrap();
 
-                let (msg, sender) = msg_from_action(&deps, actions[action_idx], user.clone());
-                rewards += extract_reward_deposit(msg.clone());
-                let result = handle(&mut deps, mock_env(sender, &[], block), msg);
-                total_rewards_output += extract_rewards(result);
-            }
+        // Allocations are kept aside while rewards are frozen
+        handle(
+            &mut deps,
+            mock_env("admin", &[], 4),
+            set_status(ContractStatusLevel::FreezeRewards),
+        )
+        .unwrap();
+        let allocation_msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(500),
+            hook: None,
+        };
+        handle(&mut deps, mock_env("admin", &[], 5), allocation_msg).unwrap();
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.acc_reward_per_share, 0);
+        assert_eq!(reward_pool.residue, 500);
 
-            if block % 10000 == 0 {
-                print_status(&deps, users.clone(), block);
-            }
+        // Only emergency redeems while fully stopped
+        handle(
+            &mut deps,
+            mock_env("admin", &[], 6),
+            set_status(ContractStatusLevel::StopAll),
+        )
+        .unwrap();
+        let claim_msg = LPStakingHandleMsg::Claim {
+            recipient: None,
+            msg: None,
+        };
+        assert!(handle(&mut deps, mock_env("alice", &[], 6), claim_msg).is_err());
 
-            deadline = TypedStore::<Config, MockStorage>::attach(&deps.storage)
-                .load(CONFIG_KEY)
-                .unwrap()
-                .deadline;
-            block += 1;
+        let query_result = query(&deps, LPStakingQueryMsg::ContractStatus {}).unwrap();
+        match from_binary(&query_result).unwrap() {
+            LPStakingQueryAnswer::ContractStatus { status } => {
+                assert_eq!(status, ContractStatusLevel::StopAll)
+            }
+            _ => panic!("Unexpected"),
         }
 
-        // Make sure all users are fully redeemed
-        for user in users.clone() {
-            let redeem_msg = LPStakingHandleMsg::Redeem { amount: None };
-            let result = handle(&mut deps, mock_env(user.0, &[], 1_700_000), redeem_msg);
-            total_rewards_output += extract_rewards(result);
-        }
+        handle(
+            &mut deps,
+            mock_env("admin", &[], 7),
+            LPStakingHandleMsg::ResumeContract {},
+        )
+        .unwrap();
+        handle(&mut deps, mock_env("eth", &[], 7), deposit_msg).unwrap();
+    }
 
-        let error = 1.0 - (total_rewards_output as f64 / rewards as f64);
-        println!("Error is: {}", error);
-        assert!(error >= 0f64 && error < 0.01);
+    // Answers every query with the same SNIP-20 balance
+    struct BalanceQuerier {
+        balance: u128,
+    }
 
-        // Do another run after first iteration is ended
-        continue_after_ended(&mut deps, deadline, actions, users);
+    impl Querier for BalanceQuerier {
+        fn raw_query(&self, _request: &[u8]) -> QuerierResult {
+            let response = format!(r#"{{"balance":{{"amount":"{}"}}}}"#, self.balance);
+            Ok(Ok(Binary::from(response.as_bytes())))
+        }
     }
 
-    fn continue_after_ended(
-        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
-        deadline: u64,
-        actions: Vec<&str>,
-        users: Vec<HumanAddr>,
-    ) {
-        let mut rng = rand::thread_rng();
+    #[test]
+    fn test_recover_tokens() {
+        let deps = init_helper(10000000);
+        let mut deps = Extern {
+            storage: deps.storage,
+            api: deps.api,
+            querier: BalanceQuerier { balance: 0 },
+        };
 
-        let start_block = deadline + 10_001;
-        let mut new_deadline = start_block + 500_000;
-        let mut rewards = 500_000_000000;
-        let mut total_rewards_output = 0;
+        deposit_helper(&mut deps, "alice", 1000, 2).unwrap();
+        let allocation_msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(500),
+            hook: None,
+        };
+        handle(&mut deps, mock_env("admin", &[], 3), allocation_msg).unwrap();
 
-        let msg = LPStakingHandleMsg::SetDeadline {
-            block: new_deadline,
+        let inc_token = SecretContract {
+            address: HumanAddr("eth".to_string()),
+            contract_hash: "2".to_string(),
         };
-        let result = handle(deps, mock_env("admin".to_string(), &[], start_block), msg);
-        let msg = LPStakingHandleMsg::Receive {
-            sender: HumanAddr("admin".to_string()),
-            from: HumanAddr("admin".to_string()),
-            amount: Uint128(rewards),
-            msg: to_binary(&LPStakingReceiveMsg::DepositRewards {}).unwrap(),
+        let reward_token = SecretContract {
+            address: HumanAddr("scrt".to_string()),
+            contract_hash: "1".to_string(),
         };
-        let result = handle(
-            deps,
-            mock_env("scrt".to_string(), &[], start_block + 1),
-            msg,
+        let stray_token = SecretContract {
+            address: HumanAddr("stray".to_string()),
+            contract_hash: "5".to_string(),
+        };
+        let recover = |token: &SecretContract, amount: u128| LPStakingHandleMsg::RecoverTokens {
+            token: token.clone(),
+            amount: Uint128(amount),
+            recipient: Some(HumanAddr("treasury".to_string())),
+        };
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("alice", &[], 4),
+            recover(&stray_token, 42),
+        );
+        assert!(handle_result.is_err());
+        let handle_response = handle(
+            &mut deps,
+            mock_env("admin", &[], 4),
+            recover(&stray_token, 42),
+        )
+        .unwrap();
+        assert_eq!(
+            handle_response.messages,
+            vec![snip20::transfer_msg(
+                HumanAddr("treasury".to_string()),
+                Uint128(42),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                "5".to_string(),
+                HumanAddr("stray".to_string()),
+            )
+            .unwrap()]
         );
 
-        let mut block = start_block + 2;
-        while block < (new_deadline + 10_000) {
-            let num_of_actions = rng.gen_range(0, 5);
+        // Only what exceeds the stakes can be recovered
+        deps.querier = BalanceQuerier { balance: 1200 };
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[], 4),
+            recover(&inc_token, 201),
+        );
+        assert!(handle_result
+            .unwrap_err()
+            .to_string()
+            .contains("surplus=200"));
+        handle(
+            &mut deps,
+            mock_env("admin", &[], 4),
+            recover(&inc_token, 200),
+        )
+        .unwrap();
+
+        // Only what exceeds the rewards owed to stakers can be recovered
+        deps.querier = BalanceQuerier { balance: 600 };
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[], 4),
+            recover(&reward_token, 101),
+        );
+        assert!(handle_result
+            .unwrap_err()
+            .to_string()
+            .contains("surplus=100"));
+
+        let claim_msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(0),
+            hook: Some(
+                to_binary(&LPStakingHookMsg::Claim {
+                    from: HumanAddr("alice".to_string()),
+                    recipient: HumanAddr("alice".to_string()),
+                    msg: None,
+                })
+                .unwrap(),
+            ),
+        };
+        handle(&mut deps, mock_env("admin", &[], 5), claim_msg).unwrap();
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.rewards_owed, 0);
 
-            for i in 0..num_of_actions {
-                let action_idx = rng.gen_range(0, actions.len());
+        deps.querier = BalanceQuerier { balance: 100 };
+        handle(
+            &mut deps,
+            mock_env("admin", &[], 5),
+            recover(&reward_token, 100),
+        )
+        .unwrap();
+    }
 
-                let user_idx = rng.gen_range(0, users.len());
-                let user = users[user_idx].clone();
+    #[test]
+    fn test_streamed_residue() {
+        let mut deps = init_helper(10000000);
 
-                let (msg, sender) = msg_from_action(&deps, actions[action_idx], user.clone());
-                rewards += extract_reward_deposit(msg.clone());
-                let result = handle(deps, mock_env(sender, &[], block), msg);
-                total_rewards_output += extract_rewards(result);
-            }
+        let policy_msg = LPStakingHandleMsg::SetResiduePolicy {
+            policy: ResiduePolicy::Stream { blocks: 10 },
+        };
+        handle(&mut deps, mock_env("admin", &[], 1), policy_msg).unwrap();
 
-            if block % 10000 == 0 {
-                print_status(&deps, users.clone(), block);
-            }
+        let allocate = |amount: u128| LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(amount),
+            hook: None,
+        };
+        handle(&mut deps, mock_env("admin", &[], 2), allocate(1000)).unwrap();
 
-            new_deadline = TypedStore::<Config, MockStorage>::attach(&deps.storage)
-                .load(CONFIG_KEY)
-                .unwrap()
-                .deadline;
-            block += 1;
-        }
+        deposit_helper(&mut deps, "alice", 1000, 3).unwrap();
 
-        // Make sure all users are fully redeemed
-        for user in users {
-            let redeem_msg = LPStakingHandleMsg::Redeem { amount: None };
-            let result = handle(deps, mock_env(user.0, &[], 1_700_000), redeem_msg);
-            total_rewards_output += extract_rewards(result);
+        // The stream starts with the first allocation that has stakers to stream to
+        handle(&mut deps, mock_env("admin", &[], 4), allocate(100)).unwrap();
+        handle(&mut deps, mock_env("admin", &[], 9), allocate(100)).unwrap();
+
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.total_rewards_distributed, 700);
+
+        let query_result = query(&deps, LPStakingQueryMsg::ResiduePolicy {}).unwrap();
+        match from_binary(&query_result).unwrap() {
+            LPStakingQueryAnswer::ResiduePolicy {
+                policy,
+                residue,
+                stream_end_block,
+            } => {
+                assert_eq!(policy, ResiduePolicy::Stream { blocks: 10 });
+                assert_eq!(residue, Uint128(500));
+                assert_eq!(stream_end_block, Some(14));
+            }
+            _ => panic!("Unexpected"),
         }
 
-        let error = 1.0 - (total_rewards_output as f64 / rewards as f64);
-        println!("Error is: {}", error);
-        assert!(error >= 0f64 && error < 0.01);
+        handle(&mut deps, mock_env("admin", &[], 14), allocate(100)).unwrap();
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.residue, 0);
+        assert_eq!(reward_pool.total_rewards_distributed, 1300);
+    }
+
+    // Answers every query as a master with the same pending rewards
+    struct PendingQuerier {
+        pending: u128,
     }
 
-    // Tests
+    impl Querier for PendingQuerier {
+        fn raw_query(&self, _request: &[u8]) -> QuerierResult {
+            let response = format!(r#"{{"pending":{{"amount":"{}"}}}}"#, self.pending);
+            Ok(Ok(Binary::from(response.as_bytes())))
+        }
+    }
 
     #[test]
-    fn test_claim_pool() {
-        let (init_result, mut deps) = init_helper(10000000); // Claim height is deadline + 1
+    fn test_rewards_query_before_residue_stream_release() {
+        let deps = init_helper(10000000);
+        let mut deps = Extern {
+            storage: deps.storage,
+            api: deps.api,
+            querier: PendingQuerier { pending: 100 },
+        };
 
-        let claim_msg = LPStakingHandleMsg::ClaimRewardPool { to: None };
-        let handle_response = handle(&mut deps, mock_env("not_admin", &[], 10), claim_msg.clone());
-        assert_eq!(
-            handle_response.unwrap_err(),
-            StdError::GenericErr {
-                msg: "not an admin: not_admin".to_string(),
-                backtrace: None
-            }
-        );
+        let policy_msg = LPStakingHandleMsg::SetResiduePolicy {
+            policy: ResiduePolicy::Stream { blocks: 10 },
+        };
+        handle(&mut deps, mock_env("admin", &[], 1), policy_msg).unwrap();
 
-        let handle_response = handle(&mut deps, mock_env("admin", &[], 10), claim_msg.clone());
-        assert_eq!(
-            handle_response.unwrap_err(),
-            StdError::GenericErr {
-                msg: format!("minimum claim height hasn't passed yet: {}", 10000001),
-                backtrace: None
-            }
-        );
+        let allocate = |amount: u128| LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(amount),
+            hook: None,
+        };
+        handle(&mut deps, mock_env("admin", &[], 2), allocate(1000)).unwrap();
+        deposit_helper(&mut deps, "alice", 1000, 3).unwrap();
+        handle(&mut deps, mock_env("admin", &[], 4), allocate(100)).unwrap();
+        handle(&mut deps, mock_env("admin", &[], 9), allocate(100)).unwrap();
 
-        let handle_response = handle(
-            &mut deps,
-            mock_env("admin", &[], 10000001),
-            claim_msg.clone(),
-        );
-        assert_eq!(
-            handle_response.unwrap_err(),
-            StdError::GenericErr {
-                msg: "Error performing Balance query: Generic error: Querier system error: No such contract: scrt".to_string(), // No way to test external queries yet
-                backtrace: None
+        let vk_msg = LPStakingHandleMsg::SetViewingKey {
+            key: "42".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[], 9), vk_msg).unwrap();
+
+        // The stream was last released at block 9, nothing more is released for block 5
+        let query_msg = LPStakingQueryMsg::Rewards {
+            address: HumanAddr("alice".to_string()),
+            key: "42".to_string(),
+            height: 5,
+            include_pending: Some(true),
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            LPStakingQueryAnswer::Rewards {
+                rewards,
+                includes_pending,
+            } => {
+                assert_eq!(rewards, Uint128(800));
+                assert!(includes_pending);
             }
-        );
+            _ => panic!("Unexpected"),
+        }
     }
 
     #[test]
-    fn test_stop_contract() {
-        let (init_result, mut deps) = init_helper(10000000);
-
-        let stop_msg = LPStakingHandleMsg::StopContract {};
-        let handle_response = handle(&mut deps, mock_env("not_admin", &[], 10), stop_msg.clone());
-        assert_eq!(
-            handle_response.unwrap_err(),
-            StdError::GenericErr {
-                msg: "not an admin: not_admin".to_string(),
-                backtrace: None
-            }
-        );
+    fn test_residue_returned_to_master() {
+        let mut deps = init_helper(10000000);
 
-        let handle_response = handle(&mut deps, mock_env("admin", &[], 10), stop_msg);
-        let unwrapped_result: LPStakingHandleAnswer =
-            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&LPStakingHandleAnswer::StopContract { status: Success }).unwrap()
-        );
+        let policy_msg = LPStakingHandleMsg::SetResiduePolicy {
+            policy: ResiduePolicy::Treasury,
+        };
+        assert!(handle(&mut deps, mock_env("admin", &[], 1), policy_msg).is_err());
 
-        let redeem_msg = LPStakingHandleMsg::Redeem { amount: None };
-        let handle_response = handle(&mut deps, mock_env("user", &[], 20), redeem_msg);
-        assert_eq!(
-            handle_response.unwrap_err(),
-            StdError::GenericErr {
-                msg: "this contract is stopped and this action is not allowed".to_string(),
-                backtrace: None
-            }
-        );
+        let policy_msg = LPStakingHandleMsg::SetResiduePolicy {
+            policy: ResiduePolicy::ReturnToMaster,
+        };
+        handle(&mut deps, mock_env("admin", &[], 1), policy_msg).unwrap();
 
-        let resume_msg = LPStakingHandleMsg::ResumeContract {};
-        let handle_response = handle(&mut deps, mock_env("admin", &[], 21), resume_msg);
-        let unwrapped_result: LPStakingHandleAnswer =
-            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
+        let allocation_msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(300),
+            hook: None,
+        };
+        let handle_response = handle(&mut deps, mock_env("admin", &[], 2), allocation_msg).unwrap();
         assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&LPStakingHandleAnswer::ResumeContract { status: Success }).unwrap()
+            handle_response.messages,
+            vec![snip20::transfer_msg(
+                HumanAddr::default(),
+                Uint128(300),
+                None,
+                RESPONSE_BLOCK_SIZE,
+                "1".to_string(),
+                HumanAddr("scrt".to_string()),
+            )
+            .unwrap()]
         );
 
-        let redeem_msg = LPStakingHandleMsg::Redeem { amount: None };
-        let handle_response = handle(&mut deps, mock_env("user", &[], 20), redeem_msg);
-        let unwrapped_result: LPStakingHandleAnswer =
-            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&LPStakingHandleAnswer::Redeem { status: Success }).unwrap()
-        );
+        let reward_pool: RewardPool = TypedStore::attach(&deps.storage)
+            .load(REWARD_POOL_KEY)
+            .unwrap();
+        assert_eq!(reward_pool.residue, 0);
+        assert_eq!(reward_pool.rewards_owed, 0);
     }
 
     #[test]
-    fn test_admin() {
-        let (init_result, mut deps) = init_helper(10000000);
+    fn test_rewards_query_without_master() {
+        let mut deps = init_helper(10000000);
 
-        let admin_action_msg = LPStakingHandleMsg::ChangeAdmin {
-            address: HumanAddr("not_admin".to_string()),
+        deposit_helper(&mut deps, "alice", 1000, 2).unwrap();
+        let allocation_msg = LPStakingHandleMsg::NotifyAllocation {
+            amount: Uint128(500),
+            hook: None,
         };
-        let handle_response = handle(&mut deps, mock_env("not_admin", &[], 1), admin_action_msg);
-        assert_eq!(
-            handle_response.unwrap_err(),
-            StdError::GenericErr {
-                msg: "not an admin: not_admin".to_string(),
-                backtrace: None
+        handle(&mut deps, mock_env("admin", &[], 3), allocation_msg).unwrap();
+
+        let vk_msg = LPStakingHandleMsg::SetViewingKey {
+            key: "42".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("alice", &[], 3), vk_msg).unwrap();
+
+        // The mocked master can't be queried, so only credited rewards are returned
+        for include_pending in vec![None, Some(true), Some(false)] {
+            let query_msg = LPStakingQueryMsg::Rewards {
+                address: HumanAddr("alice".to_string()),
+                key: "42".to_string(),
+                height: 10,
+                include_pending,
+            };
+            match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+                LPStakingQueryAnswer::Rewards {
+                    rewards,
+                    includes_pending,
+                } => {
+                    assert_eq!(rewards, Uint128(500));
+                    assert!(!includes_pending);
+                }
+                _ => panic!("Unexpected"),
             }
-        );
+        }
+    }
 
-        let admin_action_msg = LPStakingHandleMsg::ChangeAdmin {
-            address: HumanAddr("new_admin".to_string()),
+    #[test]
+    fn test_query_with_permit() {
+        let mut deps = init_helper(10000000);
+        let signer = HumanAddr("secret1wv66lgmt6azsngtdcqrkgyt7hjsk3r9lkvzqcx".to_string());
+
+        deposit_helper(&mut deps, &signer.0, 1000, 2).unwrap();
+
+        // Signed for this contract, with the `balance` permission only
+        let permit = Permit {
+            params: PermitParams {
+                allowed_tokens: vec![HumanAddr(MOCK_CONTRACT_ADDR.to_string())],
+                permit_name: "test".to_string(),
+                chain_id: "secret-4".to_string(),
+                permissions: vec![Permission::Balance],
+            },
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary::from_base64("Aq5U7xmLb4QZjttvvflKkSN8wD9ywJ8brS/IhFKlww5F")
+                        .unwrap(),
+                },
+                signature: Binary::from_base64(
+                    "q+oy5eoKpsf+shFw54rqYrc5iCnWNV1AQzQq3D1z7wshXl7gr5Qrar9l+pOdGBHk+a66+5bj64WhB9bthpH1ug==",
+                )
+                .unwrap(),
+            },
         };
-        let handle_response = handle(&mut deps, mock_env("admin", &[], 1), admin_action_msg);
-        let unwrapped_result: LPStakingHandleAnswer =
-            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&LPStakingHandleAnswer::ChangeAdmin { status: Success }).unwrap()
-        );
 
-        let admin_action_msg = LPStakingHandleMsg::ChangeAdmin {
-            address: HumanAddr("not_admin".to_string()),
+        let query_msg = LPStakingQueryMsg::WithPermit {
+            permit: permit.clone(),
+            query: LPStakingQueryWithPermit::Balance {},
         };
-        let handle_response = handle(&mut deps, mock_env("admin", &[], 1), admin_action_msg);
-        assert_eq!(
-            handle_response.unwrap_err(),
-            StdError::GenericErr {
-                msg: "not an admin: admin".to_string(),
-                backtrace: None
-            }
-        );
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            LPStakingQueryAnswer::Balance { amount } => assert_eq!(amount, Uint128(1000)),
+            _ => panic!("Unexpected"),
+        }
 
-        let admin_action_msg = LPStakingHandleMsg::ChangeAdmin {
-            address: HumanAddr("not_admin".to_string()),
+        let query_msg = LPStakingQueryMsg::WithPermit {
+            permit: permit.clone(),
+            query: LPStakingQueryWithPermit::History {
+                page: None,
+                page_size: 10,
+            },
         };
-        let handle_response = handle(&mut deps, mock_env("new_admin", &[], 1), admin_action_msg);
-        let unwrapped_result: LPStakingHandleAnswer =
-            from_binary(&handle_response.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&LPStakingHandleAnswer::ChangeAdmin { status: Success }).unwrap()
-        );
+        assert!(query(&deps, query_msg).is_err());
+
+        let revoke_msg = LPStakingHandleMsg::RevokePermit {
+            permit_name: "test".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env(signer, &[], 3), revoke_msg).unwrap();
+
+        let query_msg = LPStakingQueryMsg::WithPermit {
+            permit,
+            query: LPStakingQueryWithPermit::Balance {},
+        };
+        assert!(query(&deps, query_msg).is_err());
     }
 
     #[test]
     fn test_single_run() {
         let mut rng = rand::thread_rng();
 
-        let deadline: u64 = rng.gen_range(100_000, 5_000_000);
-        let rewards: u128 = rng.gen_range(1_000_000000, 10_000_000_000000); // 1k-10mn SCRT
+        let deadline: u64 = rng.gen_range(10_000, 50_000);
+        let rate: u128 = rng.gen_range(1_000_000, 100_000_000); // 1-100 SCRT per block
 
-        sanity_run(rewards, deadline);
+        sanity_run(rate, deadline);
     }
 
     #[test]
@@ -1201,15 +3877,15 @@ mod tests {
 
         for run in 0..100 {
             let deadline: u64 = rng.gen_range(100_000, 5_000_000);
-            let rewards: u128 = rng.gen_range(1_000_000000, 10_000_000_000000); // 1k-10mn SCRT
+            let rate: u128 = rng.gen_range(1_000_000, 100_000_000); // 1-100 SCRT per block
 
             println!("$$$$$$$$$$$$$$$$$$ Run Parameters $$$$$$$$$$$$$$$$$$");
             println!("Run number: {}", run + 1);
-            println!("Rewards: {}", rewards);
+            println!("Rewards per block: {}", rate);
             println!("Deadline: {}", deadline);
             println!();
 
-            sanity_run(rewards, deadline);
+            sanity_run(rate, deadline);
         }
     }
 
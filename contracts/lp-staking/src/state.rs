@@ -1,7 +1,13 @@
-use cosmwasm_std::HumanAddr;
-use scrt_finance::lp_staking_types::SecretContract;
+use cosmwasm_std::{HumanAddr, ReadonlyStorage, StdResult, Storage, Uint128};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use scrt_finance::lp_staking_msg::ContractStatusLevel;
+use scrt_finance::lp_staking_types::{Fees, ResiduePolicy, SecretContract, Tx, TxAction, Vesting};
+use scrt_finance::math::{add, mul_div, sub};
+use secret_toolkit::storage::{AppendStore, AppendStoreMut, TypedStore, TypedStoreMut};
 use serde::{Deserialize, Serialize};
 
+use crate::constants::{PREFIX_TXS, TX_COUNT_KEY};
+
 #[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone)]
 pub struct Config {
     pub admin: HumanAddr,
@@ -10,6 +16,122 @@ pub struct Config {
     pub master: SecretContract,
     pub viewing_key: String,
     pub prng_seed: Vec<u8>,
-    pub is_stopped: bool,
+    pub status: ContractStatusLevel,
     pub own_addr: HumanAddr,
+    pub deadline: u64,
+    // Only these addresses are allowed to deposit on behalf of others
+    pub approved_depositors: Vec<HumanAddr>,
+    // Deposit limits. `None` means unlimited, or open to everyone in the case of the allowlist
+    pub total_deposit_cap: Option<u128>,
+    pub user_deposit_cap: Option<u128>,
+    pub deposit_allowlist: Option<Vec<HumanAddr>>,
+    pub fees: Fees,
+    pub vesting: Option<Vesting>,
+    // Set once stakes can be moved to a successor contract
+    pub migration: Option<Migration>,
+    // The contract stakes may be migrated from
+    pub predecessor: Option<HumanAddr>,
+    pub residue_policy: ResiduePolicy,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone)]
+pub struct Migration {
+    pub new_contract: SecretContract,
+    pub migrated_users: u64,
+    pub migrated_amount: u128,
+}
+
+// Rewards a user harvested while vesting was enabled. `unvested` vests linearly until `end_block`
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, Default)]
+pub struct VestingSchedule {
+    pub vested: u128,
+    pub unvested: u128,
+    pub last_update_block: u64,
+    pub end_block: u64,
+}
+
+impl VestingSchedule {
+    /// Moves whatever vested since the last update from `unvested` to `vested`
+    pub fn release(&mut self, block: u64) -> StdResult<()> {
+        if block <= self.last_update_block {
+            return Ok(());
+        }
+
+        let released = if block >= self.end_block {
+            self.unvested
+        } else {
+            mul_div(
+                self.unvested,
+                (block - self.last_update_block) as u128,
+                (self.end_block - self.last_update_block) as u128,
+            )?
+        };
+        self.vested = add(self.vested, released)?;
+        self.unvested = sub(self.unvested, released)?;
+        self.last_update_block = block;
+
+        Ok(())
+    }
+
+    /// Adds newly harvested rewards. Whatever is still unvested is vested together with them, over
+    /// a whole new period
+    pub fn add(&mut self, amount: u128, block: u64, period: u64) -> StdResult<()> {
+        self.release(block)?;
+        self.unvested = add(self.unvested, amount)?;
+        self.last_update_block = block;
+        self.end_block = block.saturating_add(period);
+
+        Ok(())
+    }
+}
+
+pub fn store_tx<S: Storage>(
+    store: &mut S,
+    address: &HumanAddr,
+    action: TxAction,
+    amount: u128,
+    block_height: u64,
+    acc_reward_per_share: u128,
+) -> StdResult<()> {
+    let id = TypedStore::<u64, S>::attach(store)
+        .load(TX_COUNT_KEY)
+        .unwrap_or(0) // NotFound is the only possible error
+        + 1;
+    TypedStoreMut::<u64, S>::attach(store).store(TX_COUNT_KEY, &id)?;
+
+    let tx = Tx {
+        id,
+        action,
+        amount: Uint128(amount),
+        block_height,
+        acc_reward_per_share: Uint128(acc_reward_per_share),
+    };
+
+    let mut store = PrefixedStorage::multilevel(&[PREFIX_TXS, address.0.as_bytes()], store);
+    let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+    store.push(&tx)
+}
+
+pub fn get_txs<S: ReadonlyStorage>(
+    storage: &S,
+    address: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Vec<Tx>> {
+    let store = ReadonlyPrefixedStorage::multilevel(&[PREFIX_TXS, address.0.as_bytes()], storage);
+
+    // If the user has no history yet, there is no store to attach to
+    let store = if let Some(result) = AppendStore::<Tx, _>::attach(&store) {
+        result?
+    } else {
+        return Ok(vec![]);
+    };
+
+    // Latest txs first
+    store
+        .iter()
+        .rev()
+        .skip(page.saturating_mul(page_size) as _)
+        .take(page_size as _)
+        .collect()
 }
@@ -8,6 +8,7 @@ use scrt_finance::lp_staking_msg::LPStakingHandleMsg;
 use scrt_finance::master_msg::{MasterHandleAnswer, MasterInitMsg, MasterQueryMsg};
 use scrt_finance::master_msg::{MasterHandleMsg, MasterQueryAnswer};
 use scrt_finance::master_types::{sort_schedule, Schedule, SpySettings, WeightInfo};
+use scrt_finance::math::{add, mul, mul_div};
 use secret_toolkit::snip20;
 use secret_toolkit::storage::{TypedStore, TypedStoreMut};
 
@@ -107,7 +108,7 @@ fn set_weights<S: Storage, A: Api, Q: Querier>(
                 state.total_weight,
                 &state.minting_schedule,
                 spy_settings.clone(),
-            );
+            )?;
             messages.push(snip20::mint_msg(
                 to_update.address.clone(),
                 Uint128(rewards),
@@ -181,7 +182,7 @@ fn update_allocation<S: Storage, A: Api, Q: Querier>(
             state.total_weight,
             &state.minting_schedule,
             spy_settings.clone(),
-        );
+        )?;
         messages.push(snip20::mint_msg(
             spy_address.clone(),
             Uint128(rewards),
@@ -331,7 +332,7 @@ fn query_pending_rewards<S: Storage, A: Api, Q: Querier>(
             last_update_block: block,
         });
 
-    let amount = get_spy_rewards(block, state.total_weight, &state.minting_schedule, spy);
+    let amount = get_spy_rewards(block, state.total_weight, &state.minting_schedule, spy)?;
 
     Ok(MasterQueryAnswer::Pending {
         amount: Uint128(amount),
@@ -343,7 +344,7 @@ fn get_spy_rewards(
     total_weight: u64,
     schedule: &Schedule,
     spy_settings: SpySettings,
-) -> u128 {
+) -> StdResult<u128> {
     let mut last_update_block = spy_settings.last_update_block;
 
     let mut multiplier = 0;
@@ -351,17 +352,33 @@ fn get_spy_rewards(
     for u in schedule.to_owned() {
         if last_update_block < u.end_block {
             if current_block > u.end_block {
-                multiplier += (u.end_block - last_update_block) as u128 * u.mint_per_block.u128();
+                multiplier = add(
+                    multiplier,
+                    mul(
+                        (u.end_block - last_update_block) as u128,
+                        u.mint_per_block.u128(),
+                    )?,
+                )?;
                 last_update_block = u.end_block;
             } else {
-                multiplier += (current_block - last_update_block) as u128 * u.mint_per_block.u128();
+                multiplier = add(
+                    multiplier,
+                    mul(
+                        (current_block - last_update_block) as u128,
+                        u.mint_per_block.u128(),
+                    )?,
+                )?;
                 // last_update_block = current_block;
                 break; // No need to go further up the schedule
             }
         }
     }
 
-    (multiplier * spy_settings.weight as u128) / total_weight as u128
+    mul_div(
+        multiplier,
+        spy_settings.weight as u128,
+        total_weight as u128,
+    )
 }
 
 fn enforce_admin(config: State, env: Env) -> StdResult<()> {
@@ -0,0 +1,103 @@
+use crate::governance_types::{GovernanceConfig, Proposal, ProposalMsg, VoteOption};
+use crate::lp_staking_types::SecretContract;
+use crate::permit::Permit;
+use cosmwasm_std::{Binary, HumanAddr, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GovernanceInitMsg {
+    pub gov_token: SecretContract,
+    pub min_deposit: Uint128,
+    pub voting_period: u64,
+    pub quorum_bps: u16,
+    pub threshold_bps: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GovernanceHandleMsg {
+    Vote {
+        proposal_id: u64,
+        vote: VoteOption,
+        // A permit for the gov token with the `balance` permission, signed by the voter
+        permit: Permit,
+    },
+    // Tallies a proposal after its voting period and returns the deposit unless it expired
+    Finalize {
+        proposal_id: u64,
+    },
+    // Sends the msgs of a passed proposal. Kept apart from `Finalize` so a failing msg can't
+    // block the tally or the deposit refund
+    Execute {
+        proposal_id: u64,
+    },
+
+    // Registered commands
+    Receive {
+        sender: HumanAddr,
+        from: HumanAddr,
+        amount: Uint128,
+        msg: Binary,
+    },
+
+    // Governance commands, only callable by the contract itself through a proposal
+    UpdateConfig {
+        min_deposit: Option<Uint128>,
+        voting_period: Option<u64>,
+        quorum_bps: Option<u16>,
+        threshold_bps: Option<u16>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GovernanceReceiveMsg {
+    // The sent tokens are the deposit
+    CreateProposal {
+        title: String,
+        description: String,
+        msgs: Vec<ProposalMsg>,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GovernanceHandleAnswer {
+    CreateProposal { proposal_id: u64 },
+    Vote { status: GovernanceResponseStatus },
+    Finalize { status: GovernanceResponseStatus },
+    Execute { status: GovernanceResponseStatus },
+    UpdateConfig { status: GovernanceResponseStatus },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GovernanceQueryMsg {
+    Config {},
+    Proposal { proposal_id: u64 },
+    // Newest first
+    Proposals { page: Option<u32>, page_size: u32 },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GovernanceQueryAnswer {
+    Config {
+        config: GovernanceConfig,
+    },
+    Proposal {
+        proposal: Proposal,
+    },
+    Proposals {
+        proposals: Vec<Proposal>,
+        total: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GovernanceResponseStatus {
+    Success,
+    Failure,
+}
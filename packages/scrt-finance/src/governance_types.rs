@@ -0,0 +1,65 @@
+use crate::lp_staking_types::SecretContract;
+use cosmwasm_std::{Binary, HumanAddr, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, JsonSchema)]
+pub struct GovernanceConfig {
+    // Voting power is read from this token, and proposal deposits are paid in it
+    pub gov_token: SecretContract,
+    pub min_deposit: Uint128,
+    // In blocks
+    pub voting_period: u64,
+    // Minimum votes cast (including abstains) for a proposal to be valid, in basis points of the
+    // total voting power at the proposal's snapshot block
+    pub quorum_bps: u16,
+    // Share of the yes and no votes that must be yes for a proposal to pass, in basis points
+    pub threshold_bps: u16,
+}
+
+// Executed as a `WasmMsg::Execute` sent by the governance contract
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, JsonSchema)]
+pub struct ProposalMsg {
+    pub contract: SecretContract,
+    pub msg: Binary,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, Copy, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteOption {
+    Yes,
+    No,
+    Abstain,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, Copy, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalStatus {
+    // Also after the voting period, until the proposal is finalized
+    Voting,
+    Rejected,
+    // Didn't reach quorum. The deposit is burned
+    Expired,
+    // Waiting for `Execute`. Stays here if executing its msgs fails, so it can be retried
+    Passed,
+    Executed,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, JsonSchema)]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: HumanAddr,
+    pub title: String,
+    pub description: String,
+    pub msgs: Vec<ProposalMsg>,
+    pub deposit: Uint128,
+    // Voting power is taken as of the end of this block
+    pub snapshot_block: u64,
+    // Total voting power at `snapshot_block`, which the quorum is relative to
+    pub total_voting_power: Uint128,
+    pub end_block: u64,
+    pub yes: Uint128,
+    pub no: Uint128,
+    pub abstain: Uint128,
+    pub status: ProposalStatus,
+}
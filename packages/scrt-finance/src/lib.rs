@@ -1,7 +1,12 @@
+pub mod governance_msg;
+pub mod governance_types;
 pub mod lp_staking_msg;
 pub mod lp_staking_types;
 pub mod master_msg;
 pub mod master_types;
+pub mod math;
+pub mod padding;
+pub mod permit;
 pub mod utils;
 pub mod viewing_key;
 
@@ -1,4 +1,5 @@
-use crate::lp_staking_types::{SecretContract, TokenInfo};
+use crate::lp_staking_types::{Fees, ResiduePolicy, SecretContract, TokenInfo, Tx, Vesting};
+use crate::permit::Permit;
 use crate::viewing_key::ViewingKey;
 use cosmwasm_std::{Binary, HumanAddr, Uint128};
 use schemars::JsonSchema;
@@ -10,6 +11,10 @@ pub enum LPStakingHandleMsg {
     Redeem {
         amount: Option<Uint128>,
     },
+    Claim {
+        recipient: Option<HumanAddr>,
+        msg: Option<Binary>,
+    },
     CreateViewingKey {
         entropy: String,
         padding: Option<String>,
@@ -18,7 +23,13 @@ pub enum LPStakingHandleMsg {
         key: String,
         padding: Option<String>,
     },
+    RevokePermit {
+        permit_name: String,
+        padding: Option<String>,
+    },
     EmergencyRedeem {},
+    ExitVesting {},
+    Migrate {},
 
     // Registered commands
     Receive {
@@ -31,9 +42,51 @@ pub enum LPStakingHandleMsg {
     // Admin commands
     StopContract {},
     ResumeContract {},
+    SetContractStatus {
+        level: ContractStatusLevel,
+    },
     ChangeAdmin {
         address: HumanAddr,
     },
+    SetDeadline {
+        block: u64,
+    },
+    SetApprovedDepositors {
+        depositors: Vec<HumanAddr>,
+    },
+    SetDepositCaps {
+        total_cap: Option<Uint128>,
+        user_cap: Option<Uint128>,
+    },
+    SetDepositAllowlist {
+        allowlist: Option<Vec<HumanAddr>>,
+    },
+    SetFees {
+        fees: Fees,
+    },
+    SetVesting {
+        vesting: Option<Vesting>,
+    },
+    MigrateTo {
+        new_contract: SecretContract,
+    },
+    MigrateUsers {
+        users: Vec<HumanAddr>,
+    },
+    SetPredecessor {
+        address: Option<HumanAddr>,
+    },
+    RecoverTokens {
+        token: SecretContract,
+        amount: Uint128,
+        recipient: Option<HumanAddr>,
+    },
+    SetResiduePolicy {
+        policy: ResiduePolicy,
+    },
+    ClaimRewardPool {
+        to: Option<HumanAddr>,
+    },
 
     // Master callbacks
     NotifyAllocation {
@@ -56,20 +109,37 @@ pub struct LPStakingInitMsg {
 #[serde(rename_all = "snake_case")]
 pub enum LPStakingHandleAnswer {
     Redeem { status: LPStakingResponseStatus },
+    Claim { status: LPStakingResponseStatus },
     CreateViewingKey { key: ViewingKey },
     SetViewingKey { status: LPStakingResponseStatus },
+    RevokePermit { status: LPStakingResponseStatus },
     StopContract { status: LPStakingResponseStatus },
     ResumeContract { status: LPStakingResponseStatus },
+    SetContractStatus { status: LPStakingResponseStatus },
     ChangeAdmin { status: LPStakingResponseStatus },
     SetDeadline { status: LPStakingResponseStatus },
+    SetApprovedDepositors { status: LPStakingResponseStatus },
+    SetDepositCaps { status: LPStakingResponseStatus },
+    SetDepositAllowlist { status: LPStakingResponseStatus },
+    SetFees { status: LPStakingResponseStatus },
+    SetVesting { status: LPStakingResponseStatus },
+    MigrateTo { status: LPStakingResponseStatus },
+    SetPredecessor { status: LPStakingResponseStatus },
+    RecoverTokens { status: LPStakingResponseStatus },
+    SetResiduePolicy { status: LPStakingResponseStatus },
+    Migrate { status: LPStakingResponseStatus },
     ClaimRewardPool { status: LPStakingResponseStatus },
     EmergencyRedeem { status: LPStakingResponseStatus },
+    ExitVesting { status: LPStakingResponseStatus },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum LPStakingReceiveMsg {
     Deposit {},
+    DepositFor { beneficiary: HumanAddr },
+    // Stakes moved over from the predecessor contract
+    Migrate { beneficiary: HumanAddr },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -79,10 +149,28 @@ pub enum LPStakingHookMsg {
         from: HumanAddr,
         amount: Uint128,
     },
+    // Deposits made by someone else don't restart the beneficiary's withdrawal fee period
+    DepositFor {
+        beneficiary: HumanAddr,
+        amount: Uint128,
+    },
+    // Stakes migrated from the predecessor skip fees and deposit limits
+    ReceiveMigrated {
+        beneficiary: HumanAddr,
+        amount: Uint128,
+    },
     Redeem {
         to: HumanAddr,
         amount: Option<Uint128>,
     },
+    Claim {
+        from: HumanAddr,
+        recipient: HumanAddr,
+        msg: Option<Binary>,
+    },
+    Migrate {
+        users: Vec<HumanAddr>,
+    },
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Debug)]
@@ -99,17 +187,42 @@ pub enum LPStakingQueryMsg {
     ContractStatus {},
     RewardToken {},
     IncentivizedToken {},
+    Deadline {},
+    ApprovedDepositors {},
+    PoolInfo {},
+    DepositCapacity {},
+    Fees {},
+    VestingConfig {},
+    MigrationStatus {},
+    ResiduePolicy {},
 
     // Authenticated
     Rewards {
         address: HumanAddr,
         key: String,
         height: u64,
+        // Defaults to true. Skipping the master's pending rewards gives cheaper, exact numbers
+        include_pending: Option<bool>,
     },
     Balance {
         address: HumanAddr,
         key: String,
     },
+    History {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    Vesting {
+        address: HumanAddr,
+        key: String,
+        height: u64,
+    },
+    WithPermit {
+        permit: Permit,
+        query: LPStakingQueryWithPermit,
+    },
 }
 
 impl LPStakingQueryMsg {
@@ -117,11 +230,31 @@ impl LPStakingQueryMsg {
         match self {
             LPStakingQueryMsg::Rewards { address, key, .. } => (address, ViewingKey(key.clone())),
             LPStakingQueryMsg::Balance { address, key } => (address, ViewingKey(key.clone())),
+            LPStakingQueryMsg::History { address, key, .. } => (address, ViewingKey(key.clone())),
+            LPStakingQueryMsg::Vesting { address, key, .. } => (address, ViewingKey(key.clone())),
             _ => panic!("This should never happen"),
         }
     }
 }
 
+// Same as the authenticated queries, for the account that signed the permit
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LPStakingQueryWithPermit {
+    Rewards {
+        height: u64,
+        include_pending: Option<bool>,
+    },
+    Balance {},
+    History {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    Vesting {
+        height: u64,
+    },
+}
+
 #[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum LPStakingQueryAnswer {
@@ -133,12 +266,17 @@ pub enum LPStakingQueryAnswer {
     },
     Rewards {
         rewards: Uint128,
+        // Whether rewards the master hasn't allocated to this pool yet are included
+        includes_pending: bool,
     },
     Balance {
         amount: Uint128,
     },
+    History {
+        txs: Vec<Tx>,
+    },
     ContractStatus {
-        is_stopped: bool,
+        status: ContractStatusLevel,
     },
     RewardToken {
         token: SecretContract,
@@ -146,6 +284,48 @@ pub enum LPStakingQueryAnswer {
     IncentivizedToken {
         token: SecretContract,
     },
+    Deadline {
+        block: u64,
+    },
+    ApprovedDepositors {
+        depositors: Vec<HumanAddr>,
+    },
+    PoolInfo {
+        total_locked: Uint128,
+        acc_reward_per_share: Uint128,
+        residue: Uint128,
+        last_reward_block: u64,
+        total_rewards_distributed: Uint128,
+    },
+    DepositCapacity {
+        total_cap: Option<Uint128>,
+        user_cap: Option<Uint128>,
+        // How much more can be deposited to the pool before hitting `total_cap`
+        remaining: Option<Uint128>,
+        allowlist: Option<Vec<HumanAddr>>,
+    },
+    Fees {
+        fees: Fees,
+    },
+    VestingConfig {
+        vesting: Option<Vesting>,
+    },
+    MigrationStatus {
+        new_contract: Option<SecretContract>,
+        migrated_users: u64,
+        migrated_amount: Uint128,
+        remaining_amount: Uint128,
+    },
+    ResiduePolicy {
+        policy: ResiduePolicy,
+        residue: Uint128,
+        stream_end_block: Option<u64>,
+    },
+    Vesting {
+        vested: Uint128,
+        unvested: Uint128,
+        end_block: u64,
+    },
 
     QueryError {
         msg: String,
@@ -158,3 +338,16 @@ pub enum LPStakingResponseStatus {
     Success,
     Failure,
 }
+
+// Each level also includes the restrictions of the levels before it
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractStatusLevel {
+    NormalRun,
+    // No new deposits. Redeems and claims still work
+    StopDeposits,
+    // Allocations are no longer credited to stakers, and are kept until rewards are unfrozen
+    FreezeRewards,
+    // Only `EmergencyRedeem` is allowed
+    StopAll,
+}
@@ -1,4 +1,4 @@
-use cosmwasm_std::HumanAddr;
+use cosmwasm_std::{HumanAddr, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -8,6 +8,19 @@ pub struct RewardPool {
     pub residue: u128,
     pub inc_token_supply: u128,
     pub acc_reward_per_share: u128,
+    pub last_reward_block: u64,
+    // Rewards accumulated up to this value were reclaimed by the admin after the deadline
+    pub claimed_reward_per_share: u128,
+    // All rewards ever credited to stakers through the accumulator
+    pub total_rewards_distributed: u128,
+    // Rewards credited to stakers, or kept for them, that haven't been paid out yet
+    pub rewards_owed: u128,
+    // Rewards held in vesting schedules. They're part of `rewards_owed`, and aren't swept when the
+    // admin claims the reward pool
+    pub vesting_outstanding: u128,
+    // The residue is being released to stakers between these blocks, if `residue_stream_end != 0`
+    pub residue_stream_start: u64,
+    pub residue_stream_end: u64,
 }
 
 #[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, JsonSchema)]
@@ -27,3 +40,55 @@ pub struct SecretContract {
     pub address: HumanAddr,
     pub contract_hash: String,
 }
+
+// Fees are in basis points, and are paid in the incentivized token
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, JsonSchema)]
+pub struct Fees {
+    pub deposit_fee_bps: u16,
+    pub withdraw_fee_bps: u16,
+    // Redeeming within this many blocks of the user's last deposit incurs the withdrawal fee
+    pub withdraw_fee_period: u64,
+    pub treasury: Option<SecretContract>,
+}
+
+// When set, harvested rewards vest linearly over `period` blocks instead of being paid out at once
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, JsonSchema)]
+pub struct Vesting {
+    pub period: u64,
+    // Cut of the unvested rewards that's forfeited to the other stakers when exiting early
+    pub early_exit_penalty_bps: u16,
+}
+
+// What happens to rewards allocated while nothing is staked
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResiduePolicy {
+    // Everything goes to the stakers on the next allocation, which favors whoever stakes first
+    FirstStaker,
+    // Released to the stakers gradually, over this many blocks
+    Stream { blocks: u64 },
+    // Sent to the fees treasury
+    Treasury,
+    // Sent back to the master contract
+    ReturnToMaster,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TxAction {
+    Deposit,
+    Redeem,
+    ClaimRewards,
+    EmergencyRedeem,
+    Migrate,
+}
+
+// A single entry in a user's activity history
+#[derive(Serialize, Deserialize, Eq, PartialEq, Debug, Clone, JsonSchema)]
+pub struct Tx {
+    pub id: u64,
+    pub action: TxAction,
+    pub amount: Uint128,
+    pub block_height: u64,
+    pub acc_reward_per_share: Uint128,
+}
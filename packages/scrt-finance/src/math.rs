@@ -0,0 +1,168 @@
+use cosmwasm_std::{StdError, StdResult};
+
+const LOW_MASK: u128 = u64::MAX as u128;
+
+/// A minimal unsigned 256-bit integer, used only as an intermediate value so that products of two
+/// `u128`s (e.g. an 18-decimals balance times a scaled accumulator) never overflow
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct U256 {
+    pub hi: u128,
+    pub lo: u128,
+}
+
+impl U256 {
+    pub fn from_u128(n: u128) -> Self {
+        Self { hi: 0, lo: n }
+    }
+
+    /// Multiplies two u128s into a U256. This can never overflow
+    pub fn full_mul(a: u128, b: u128) -> Self {
+        let (a1, a0) = (a >> 64, a & LOW_MASK);
+        let (b1, b0) = (b >> 64, b & LOW_MASK);
+
+        let p00 = a0 * b0;
+        let p01 = a0 * b1;
+        let p10 = a1 * b0;
+        let p11 = a1 * b1;
+
+        // At most 3 * (2^64 - 1), so it fits comfortably
+        let mid = (p00 >> 64) + (p01 & LOW_MASK) + (p10 & LOW_MASK);
+
+        Self {
+            hi: p11 + (p01 >> 64) + (p10 >> 64) + (mid >> 64),
+            lo: (p00 & LOW_MASK) | (mid << 64),
+        }
+    }
+
+    /// Divides by a u128, failing if the divisor is zero or if the quotient doesn't fit in a u128
+    pub fn checked_div_u128(self, divisor: u128) -> StdResult<u128> {
+        if divisor == 0 {
+            return Err(StdError::generic_err("math error: division by zero"));
+        }
+        if self.hi == 0 {
+            return Ok(self.lo / divisor);
+        }
+        if self.hi >= divisor {
+            return Err(StdError::generic_err(
+                "math error: result does not fit in 128 bits",
+            ));
+        }
+
+        // Long division, bit by bit. Since `hi < divisor` the quotient fits in 128 bits and the
+        // remainder always stays below the divisor
+        let mut remainder = self.hi;
+        let mut quotient: u128 = 0;
+        for i in (0..128).rev() {
+            let carry = remainder >> 127;
+            remainder = (remainder << 1) | ((self.lo >> i) & 1);
+            quotient <<= 1;
+            if carry == 1 || remainder >= divisor {
+                // If there was a carry, the real remainder is 2^128 + remainder, which is less than
+                // 2 * divisor, so the wrapping subtraction yields the correct value
+                remainder = remainder.wrapping_sub(divisor);
+                quotient |= 1;
+            }
+        }
+
+        Ok(quotient)
+    }
+}
+
+/// Calculates `a * b / denom` without overflowing on the intermediate product
+pub fn mul_div(a: u128, b: u128, denom: u128) -> StdResult<u128> {
+    U256::full_mul(a, b).checked_div_u128(denom)
+}
+
+pub fn add(a: u128, b: u128) -> StdResult<u128> {
+    a.checked_add(b).ok_or_else(|| {
+        StdError::generic_err(format!("math error: overflow when adding {} to {}", b, a))
+    })
+}
+
+pub fn sub(a: u128, b: u128) -> StdResult<u128> {
+    a.checked_sub(b).ok_or_else(|| {
+        StdError::generic_err(format!(
+            "math error: underflow when subtracting {} from {}",
+            b, a
+        ))
+    })
+}
+
+pub fn mul(a: u128, b: u128) -> StdResult<u128> {
+    a.checked_mul(b).ok_or_else(|| {
+        StdError::generic_err(format!(
+            "math error: overflow when multiplying {} by {}",
+            a, b
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCALE: u128 = 1_000_000_000_000; // 10 ^ 12
+    const ONE_TOKEN: u128 = 1_000_000_000_000_000_000; // 10 ^ 18
+
+    #[test]
+    fn test_full_mul() {
+        assert_eq!(U256::full_mul(0, u128::MAX), U256::from_u128(0));
+        assert_eq!(U256::full_mul(3, 7), U256::from_u128(21));
+        assert_eq!(U256::full_mul(1 << 64, 1 << 64), U256 { hi: 1, lo: 0 });
+        // (2^128 - 1)^2 = 2^256 - 2^129 + 1
+        assert_eq!(
+            U256::full_mul(u128::MAX, u128::MAX),
+            U256 {
+                hi: u128::MAX - 1,
+                lo: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_mul_div_extremes() {
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX).unwrap(), u128::MAX);
+        assert_eq!(mul_div(u128::MAX, 2, 2).unwrap(), u128::MAX);
+        assert_eq!(mul_div(u128::MAX, 3, 4).unwrap(), u128::MAX / 4 * 3 + 2);
+        assert_eq!(mul_div(1 << 127, 4, 8).unwrap(), 1 << 126);
+        assert_eq!(
+            mul_div(u128::MAX, u128::MAX - 1, u128::MAX).unwrap(),
+            u128::MAX - 1
+        );
+
+        assert!(mul_div(u128::MAX, 2, 1).is_err());
+        assert!(mul_div(u128::MAX, u128::MAX, u128::MAX - 1).is_err());
+        assert!(mul_div(1, 1, 0).is_err());
+    }
+
+    #[test]
+    fn test_mul_div_token_supply() {
+        // A trillion 18-decimals tokens staked, with an accumulator that already overflows a
+        // plain u128 product
+        let locked = 1_000_000_000_000 * ONE_TOKEN;
+        let acc_reward_per_share = 100_000_000 * SCALE;
+        assert!(locked.checked_mul(acc_reward_per_share).is_none());
+        assert_eq!(
+            mul_div(locked, acc_reward_per_share, SCALE).unwrap(),
+            locked * 100_000_000
+        );
+
+        // Rewards spread over a single wei of supply
+        let rewards = 10_000_000 * ONE_TOKEN;
+        assert_eq!(mul_div(rewards, SCALE, 1).unwrap(), rewards * SCALE);
+
+        // Rewards spread over the largest possible supply round down to 0
+        assert_eq!(mul_div(rewards, SCALE, u128::MAX).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_checked_ops() {
+        assert_eq!(add(1, 2).unwrap(), 3);
+        assert_eq!(sub(3, 2).unwrap(), 1);
+        assert_eq!(mul(3, 2).unwrap(), 6);
+
+        assert!(add(u128::MAX, 1).is_err());
+        assert!(sub(0, 1).is_err());
+        assert!(mul(u128::MAX, 2).is_err());
+    }
+}
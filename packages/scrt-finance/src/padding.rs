@@ -0,0 +1,87 @@
+//! Responses are padded so their length doesn't reveal what they contain, e.g. the number of
+//! digits in a balance or the number of txs in a history page.
+use cosmwasm_std::{Binary, HandleResponse, StdResult};
+
+// Take a Vec<u8> and pad it up to a multiple of `block_size`, using spaces at the end.
+pub fn space_pad(block_size: usize, message: &mut Vec<u8>) -> &mut Vec<u8> {
+    let len = message.len();
+    let surplus = len % block_size;
+    if surplus == 0 {
+        return message;
+    }
+
+    let missing = block_size - surplus;
+    message.reserve(missing);
+    message.extend(std::iter::repeat(b' ').take(missing));
+    message
+}
+
+pub fn pad_handle_result(
+    response: StdResult<HandleResponse>,
+    block_size: usize,
+) -> StdResult<HandleResponse> {
+    response.map(|mut response| {
+        response.data = response.data.map(|mut data| {
+            space_pad(block_size, &mut data.0);
+            data
+        });
+        response
+    })
+}
+
+pub fn pad_query_result(response: StdResult<Binary>, block_size: usize) -> StdResult<Binary> {
+    response.map(|mut response| {
+        space_pad(block_size, &mut response.0);
+        response
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{from_binary, to_binary, Uint128};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum Answer {
+        Balance { amount: Uint128 },
+    }
+
+    #[test]
+    fn test_space_pad() {
+        for (len, padded_len) in &[(0, 0), (1, 256), (255, 256), (256, 256), (257, 512)] {
+            let mut message = vec![b'a'; *len];
+            assert_eq!(space_pad(256, &mut message).len(), *padded_len);
+            assert!(message[*len..].iter().all(|byte| *byte == b' '));
+        }
+    }
+
+    #[test]
+    fn test_pad_query_result() {
+        for amount in &[0, 1_000, u128::MAX] {
+            let answer = Answer::Balance {
+                amount: Uint128(*amount),
+            };
+            let padded = pad_query_result(to_binary(&answer), 256).unwrap();
+            assert_eq!(padded.len(), 256);
+
+            // Trailing spaces are valid JSON whitespace
+            assert_eq!(from_binary::<Answer>(&padded).unwrap(), answer);
+        }
+    }
+
+    #[test]
+    fn test_pad_handle_result() {
+        let response = HandleResponse {
+            messages: vec![],
+            log: vec![],
+            data: Some(Binary::from(b"{}".to_vec())),
+        };
+        let padded = pad_handle_result(Ok(response), 256).unwrap();
+        assert_eq!(padded.data.unwrap().len(), 256);
+
+        let padded = pad_handle_result(Ok(HandleResponse::default()), 256).unwrap();
+        assert_eq!(padded.data, None);
+    }
+}
@@ -0,0 +1,273 @@
+//! SNIP-24 query permits: https://github.com/SecretFoundation/SNIPs/blob/master/SNIP-24.md
+use std::convert::TryFrom;
+
+use bech32::ToBase32;
+use cosmwasm_std::{
+    to_binary, Binary, HumanAddr, ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use k256::ecdsa::signature::Verifier;
+use k256::ecdsa::{Signature, VerifyingKey};
+use ripemd160::{Digest, Ripemd160};
+use schemars::JsonSchema;
+use secret_toolkit::crypto::sha_256;
+use serde::{Deserialize, Serialize};
+
+pub const PREFIX_REVOKED_PERMITS: &[u8] = b"revoked_permits";
+pub const BECH32_HRP: &str = "secret";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Permit {
+    pub params: PermitParams,
+    pub signature: PermitSignature,
+}
+
+impl Permit {
+    pub fn check_permission(&self, permission: &Permission) -> bool {
+        self.params.permissions.contains(permission)
+            || self.params.permissions.contains(&Permission::Owner)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PermitParams {
+    pub allowed_tokens: Vec<HumanAddr>,
+    pub permit_name: String,
+    pub chain_id: String,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PermitSignature {
+    pub pub_key: PubKey,
+    pub signature: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PubKey {
+    /// ignored, but must be "tendermint/PubKeySecp256k1" otherwise the verification will fail
+    pub r#type: String,
+    /// Secp256k1 compressed public key
+    pub value: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// Allowance for SNIP-20 - Permission to query allowance of the owner & spender
+    Allowance,
+    /// Balance for SNIP-20 - Permission to query balance (and staking rewards)
+    Balance,
+    /// History for SNIP-20 - Permission to query transfer_history & transaction_history
+    History,
+    /// Owner permission indicates that the bearer of this permit should be granted all
+    /// the access of the creator/signer of the permit
+    Owner,
+}
+
+// The document that's actually signed by the wallet, in the canonical amino JSON form. Fields must
+// stay in alphabetical order
+
+#[derive(Serialize)]
+struct SignedPermit<'a> {
+    account_number: Uint128,
+    chain_id: &'a str,
+    fee: Fee,
+    memo: &'a str,
+    msgs: Vec<PermitMsg<'a>>,
+    sequence: Uint128,
+}
+
+#[derive(Serialize)]
+struct Fee {
+    amount: Vec<FeeCoin>,
+    gas: Uint128,
+}
+
+#[derive(Serialize)]
+struct FeeCoin {
+    amount: Uint128,
+    denom: &'static str,
+}
+
+#[derive(Serialize)]
+struct PermitMsg<'a> {
+    r#type: &'static str,
+    value: PermitContent<'a>,
+}
+
+#[derive(Serialize)]
+struct PermitContent<'a> {
+    allowed_tokens: &'a [HumanAddr],
+    permissions: &'a [Permission],
+    permit_name: &'a str,
+}
+
+impl<'a> SignedPermit<'a> {
+    fn from_params(params: &'a PermitParams) -> Self {
+        Self {
+            account_number: Uint128(0),
+            chain_id: &params.chain_id,
+            fee: Fee {
+                amount: vec![FeeCoin {
+                    amount: Uint128(0),
+                    denom: "uscrt",
+                }],
+                gas: Uint128(1),
+            },
+            memo: "",
+            msgs: vec![PermitMsg {
+                r#type: "query_permit",
+                value: PermitContent {
+                    allowed_tokens: &params.allowed_tokens,
+                    permissions: &params.permissions,
+                    permit_name: &params.permit_name,
+                },
+            }],
+            sequence: Uint128(0),
+        }
+    }
+}
+
+/// Verifies the permit was signed for this contract and wasn't revoked, and returns the address
+/// of the account that signed it
+pub fn validate<S: ReadonlyStorage>(
+    storage: &S,
+    permit: &Permit,
+    contract_address: &HumanAddr,
+) -> StdResult<HumanAddr> {
+    if !permit.params.allowed_tokens.contains(contract_address) {
+        return Err(StdError::generic_err(format!(
+            "permit doesn't apply to token {:?}, allowed tokens: {:?}",
+            contract_address.0,
+            permit
+                .params
+                .allowed_tokens
+                .iter()
+                .map(|a| a.0.as_str())
+                .collect::<Vec<&str>>()
+        )));
+    }
+
+    let signed_bytes = to_binary(&SignedPermit::from_params(&permit.params))?;
+    let pubkey = &permit.signature.pub_key.value;
+    let verifying_key = VerifyingKey::from_sec1_bytes(pubkey.as_slice())
+        .map_err(|_| StdError::generic_err("invalid permit public key"))?;
+    let signature = Signature::try_from(permit.signature.signature.as_slice())
+        .map_err(|_| StdError::generic_err("invalid permit signature"))?;
+    verifying_key
+        .verify(signed_bytes.as_slice(), &signature)
+        .map_err(|_| StdError::generic_err("failed to verify permit signature"))?;
+
+    let account = pubkey_to_account(pubkey)?;
+    if is_permit_revoked(storage, &account, &permit.params.permit_name) {
+        return Err(StdError::generic_err(format!(
+            "permit {:?} was revoked by account {:?}",
+            permit.params.permit_name, account.0
+        )));
+    }
+
+    Ok(account)
+}
+
+/// Derives the address of the account that owns this (compressed secp256k1) public key
+pub fn pubkey_to_account(pubkey: &Binary) -> StdResult<HumanAddr> {
+    let mut hasher = Ripemd160::new();
+    hasher.update(sha_256(pubkey.as_slice()));
+    let address = bech32::encode(BECH32_HRP, hasher.finalize().to_base32())
+        .map_err(|err| StdError::generic_err(format!("failed to encode address: {}", err)))?;
+
+    Ok(HumanAddr(address))
+}
+
+pub fn revoke_permit<S: Storage>(storage: &mut S, account: &HumanAddr, permit_name: &str) {
+    let mut store =
+        PrefixedStorage::multilevel(&[PREFIX_REVOKED_PERMITS, account.0.as_bytes()], storage);
+    store.set(permit_name.as_bytes(), &[1]);
+}
+
+pub fn is_permit_revoked<S: ReadonlyStorage>(
+    storage: &S,
+    account: &HumanAddr,
+    permit_name: &str,
+) -> bool {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[PREFIX_REVOKED_PERMITS, account.0.as_bytes()],
+        storage,
+    );
+    store.get(permit_name.as_bytes()).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+
+    const TOKEN: &str = "secret1token";
+    const SIGNER: &str = "secret1wv66lgmt6azsngtdcqrkgyt7hjsk3r9lkvzqcx";
+
+    fn test_permit() -> Permit {
+        Permit {
+            params: PermitParams {
+                allowed_tokens: vec![HumanAddr(TOKEN.to_string())],
+                permit_name: "test".to_string(),
+                chain_id: "secret-4".to_string(),
+                permissions: vec![Permission::Balance],
+            },
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary::from_base64("Aq5U7xmLb4QZjttvvflKkSN8wD9ywJ8brS/IhFKlww5F")
+                        .unwrap(),
+                },
+                signature: Binary::from_base64(
+                    "VI3hhG5BRmTVbhra//SaXZrenpFrfDLX0XRpWjqitGBdOIduUw9e8uCOm2ACbMCYSqcq/Knvk2XyE3kaugpdRA==",
+                )
+                .unwrap(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_validate_permit() {
+        let mut storage = MockStorage::new();
+        let permit = test_permit();
+        let token = HumanAddr(TOKEN.to_string());
+
+        let account = validate(&storage, &permit, &token).unwrap();
+        assert_eq!(account, HumanAddr(SIGNER.to_string()));
+        assert!(permit.check_permission(&Permission::Balance));
+        assert!(!permit.check_permission(&Permission::History));
+
+        // Wrong token
+        let err = validate(&storage, &permit, &HumanAddr("secret1other".to_string())).unwrap_err();
+        assert!(err.to_string().contains("doesn't apply to token"));
+
+        // Tampered params
+        let mut tampered = permit.clone();
+        tampered.params.permissions = vec![Permission::Owner];
+        let err = validate(&storage, &tampered, &token).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("failed to verify permit signature"));
+
+        // Revoked
+        revoke_permit(&mut storage, &account, "test");
+        assert!(is_permit_revoked(&storage, &account, "test"));
+        let err = validate(&storage, &permit, &token).unwrap_err();
+        assert!(err.to_string().contains("was revoked"));
+    }
+
+    #[test]
+    fn test_owner_permission() {
+        let mut permit = test_permit();
+        permit.params.permissions = vec![Permission::Owner];
+        assert!(permit.check_permission(&Permission::Allowance));
+        assert!(permit.check_permission(&Permission::Balance));
+        assert!(permit.check_permission(&Permission::History));
+    }
+}